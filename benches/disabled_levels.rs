@@ -0,0 +1,86 @@
+//! Cost of log macros whose level is disabled
+//!
+//! Compares `trace!` in a `#[params]` function with plain `tracing::trace!` while the
+//! subscriber only records `info` and above: a disabled call should not build the context.
+//! An enabled `info!` is included for scale.
+//!
+//! The async cases measure one call of an async `#[params]` function: without `span`
+//! the body runs as is, with it the body is boxed to carry its own context.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use log_args::params;
+
+const CALLS: u64 = 100;
+
+#[params(span, fields(order_id, customer))]
+fn params_trace(order_id: u64, customer: &str) {
+    for i in 0..CALLS {
+        trace!(i, "Hot loop");
+    }
+}
+
+fn plain_trace(order_id: u64, customer: &str) {
+    for i in 0..CALLS {
+        tracing::trace!(order_id, customer, i, "Hot loop");
+    }
+}
+
+#[params(span, fields(order_id, customer))]
+fn params_info(order_id: u64, customer: &str) {
+    for i in 0..CALLS {
+        info!(i, "Hot loop");
+    }
+}
+
+#[params(fields(order_id, customer))]
+async fn params_async(order_id: u64, customer: &str) {
+    trace!("Async call");
+}
+
+#[params(span, fields(order_id, customer))]
+async fn params_async_span(order_id: u64, customer: &str) {
+    trace!("Async call");
+}
+
+async fn plain_async(order_id: u64, customer: &str) {
+    tracing::trace!(order_id, customer, "Async call");
+}
+
+fn disabled_levels(c: &mut Criterion) {
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::INFO)
+        .with_writer(std::io::sink)
+        .finish();
+    let _default = tracing::subscriber::set_default(subscriber);
+
+    let mut group = c.benchmark_group("100 calls");
+    group.bench_function("params trace! (disabled)", |b| {
+        b.iter(|| params_trace(black_box(7), black_box("acme")))
+    });
+    group.bench_function("tracing::trace! (disabled)", |b| {
+        b.iter(|| plain_trace(black_box(7), black_box("acme")))
+    });
+    group.bench_function("params info! (enabled)", |b| {
+        b.iter(|| params_info(black_box(7), black_box("acme")))
+    });
+    group.finish();
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("async call");
+    group.bench_function("params async fn", |b| {
+        b.iter(|| runtime.block_on(params_async(black_box(7), black_box("acme"))))
+    });
+    group.bench_function("params async fn with span", |b| {
+        b.iter(|| runtime.block_on(params_async_span(black_box(7), black_box("acme"))))
+    });
+    group.bench_function("plain async fn", |b| {
+        b.iter(|| runtime.block_on(plain_async(black_box(7), black_box("acme"))))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, disabled_levels);
+criterion_main!(benches);
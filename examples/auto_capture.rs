@@ -1,5 +1,7 @@
 use log_args::params;
 use log_args_runtime::{auto_capture_context, push_context};
+// Inside `#[params]` functions the log macros resolve to the context-aware redefinitions
+#[allow(unused_imports)]
 use tracing::{info, Level};
 
 fn init_subscriber() {
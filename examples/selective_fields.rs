@@ -1,4 +1,6 @@
 use log_args::params;
+// Inside `#[params]` functions the log macros resolve to the context-aware redefinitions
+#[allow(unused_imports)]
 use tracing::{info, Level};
 
 fn init_subscriber() {
@@ -1,5 +1,7 @@
 use log_args::params;
 use tokio::task;
+// Inside `#[params]` functions the log macros resolve to the context-aware redefinitions
+#[allow(unused_imports)]
 use tracing::{debug, info, Level};
 
 fn init_subscriber() {
@@ -0,0 +1,205 @@
+//! Audit trail of who did what, kept apart from operational logs
+//!
+//! `#[params(audit(actor = ..., action = ..., resource = ...))]` starts an [`AuditGuard`]
+//! that records an [`AuditEvent`] when the call starts and another with its [`Outcome`]
+//! when it ends. Both carry the inherited context. Audit events are never sampled, rate
+//! limited or deduplicated.
+//!
+//! Events go to the installed [`AuditSink`]. The default, [`TracingSink`], emits them as
+//! `tracing` events on the fixed [`TARGET`], so a subscriber can route that target on its
+//! own. [`set_sink`] replaces it, e.g. with a [`WriterSink`] appending JSON lines to a file:
+//!
+//! ```no_run
+//! use log_args_runtime::audit::{self, WriterSink};
+//!
+//! let file = std::fs::File::create("audit.log").unwrap();
+//! audit::set_sink(WriterSink::new(file));
+//! ```
+
+use crate::wide_event::Outcome;
+use crate::ContextFrame;
+use once_cell::sync::Lazy;
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+
+/// Target of the `tracing` events emitted by [`TracingSink`]
+pub const TARGET: &str = "audit";
+
+/// Whether an [`AuditEvent`] marks the start or the end of a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditPhase {
+    Start,
+    Outcome,
+}
+
+impl AuditPhase {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditPhase::Start => "start",
+            AuditPhase::Outcome => "outcome",
+        }
+    }
+}
+
+/// Who did what to which resource, as declared by `audit(...)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    pub function: &'static str,
+    pub actor: String,
+    pub action: String,
+    pub resource: String,
+    /// Additional `key = value` pairs from `audit(...)`
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// One entry of the audit trail
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEvent {
+    pub timestamp: SystemTime,
+    pub phase: AuditPhase,
+    /// `None` for [`AuditPhase::Start`]
+    pub outcome: Option<Outcome>,
+    pub record: AuditRecord,
+    /// Context inherited from the call tree when the event was recorded
+    pub context: ContextFrame,
+}
+
+impl AuditEvent {
+    /// The event as a JSON object, with the record's fields at the top level
+    pub fn to_json(&self) -> serde_json::Value {
+        let timestamp = self
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64);
+        let mut event = serde_json::json!({
+            "timestamp_ms": timestamp,
+            "phase": self.phase.as_str(),
+            "function": self.record.function,
+            "actor": self.record.actor,
+            "action": self.record.action,
+            "resource": self.record.resource,
+        });
+        if let Some(outcome) = self.outcome {
+            event["outcome"] = outcome.as_str().into();
+        }
+        for (key, value) in &self.record.fields {
+            event[*key] = value.as_str().into();
+        }
+        event["context"] = self
+            .context
+            .iter()
+            .map(|(key, value)| (key.clone(), serde_json::Value::from(value.as_str())))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+        event
+    }
+}
+
+/// Destination of audit events
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: &AuditEvent);
+}
+
+/// Default sink: `info` events on the `audit` target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TracingSink;
+
+impl AuditSink for TracingSink {
+    fn record(&self, event: &AuditEvent) {
+        let record = &event.record;
+        tracing::info!(
+            target: TARGET,
+            phase = event.phase.as_str(),
+            function = record.function,
+            actor = %record.actor,
+            action = %record.action,
+            resource = %record.resource,
+            outcome = event.outcome.map(|outcome| outcome.as_str()),
+            fields = ?record.fields,
+            context = ?event.context,
+            "audit"
+        );
+    }
+}
+
+/// Sink writing each event as one JSON line
+pub struct WriterSink<W> {
+    writer: Mutex<W>,
+}
+
+impl<W: Write + Send> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+}
+
+impl<W: Write + Send> AuditSink for WriterSink<W> {
+    fn record(&self, event: &AuditEvent) {
+        if let Ok(mut writer) = self.writer.lock() {
+            // An audit line is either written whole or reported, never silently cut short
+            if let Err(error) = writeln!(writer, "{}", event.to_json()).and_then(|_| writer.flush())
+            {
+                tracing::error!(target: TARGET, %error, "Failed to write audit event");
+            }
+        }
+    }
+}
+
+static SINK: Lazy<RwLock<Arc<dyn AuditSink>>> = Lazy::new(|| RwLock::new(Arc::new(TracingSink)));
+
+/// Route audit events to `sink` instead of the current one
+pub fn set_sink(sink: impl AuditSink + 'static) {
+    if let Ok(mut current) = SINK.write() {
+        *current = Arc::new(sink);
+    }
+}
+
+/// Send an event to the installed sink
+pub fn record(event: &AuditEvent) {
+    let sink = SINK.read().map(|sink| Arc::clone(&sink)).ok();
+    if let Some(sink) = sink {
+        sink.record(event);
+    }
+}
+
+/// Record the start of an audited call; the outcome is recorded when the guard drops
+pub fn start(record: AuditRecord) -> AuditGuard {
+    let guard = AuditGuard {
+        record,
+        failed: false,
+    };
+    guard.emit(AuditPhase::Start, None);
+    guard
+}
+
+/// Guard returned by [`start`] that records the call's outcome on drop
+pub struct AuditGuard {
+    record: AuditRecord,
+    failed: bool,
+}
+
+impl AuditGuard {
+    /// Mark the call as failed, e.g. because it returned `Err`
+    pub fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+
+    fn emit(&self, phase: AuditPhase, outcome: Option<Outcome>) {
+        record(&AuditEvent {
+            timestamp: SystemTime::now(),
+            phase,
+            outcome,
+            record: self.record.clone(),
+            context: crate::get_context(),
+        });
+    }
+}
+
+impl Drop for AuditGuard {
+    fn drop(&mut self) {
+        self.emit(AuditPhase::Outcome, Some(Outcome::current(self.failed)));
+    }
+}
@@ -0,0 +1,265 @@
+//! Tail-based buffering: keep the debug trail of a call tree, write it only on failure
+//!
+//! `#[params(buffer)]` starts a [`BufferGuard`]. While it is active, events less severe
+//! than the threshold are held in a ring buffer shared by every function of the call
+//! tree, including async children and tasks spawned from it. The buffer lives in the
+//! call tree's context frame, so other tasks running on the same thread are not
+//! affected. It is flushed when an `ERROR` event is logged or the function returns `Err`
+//! (or panics), and discarded when the function succeeds.
+//!
+//! Buffering happens at the writer, so flushed lines keep their original timestamps.
+//! Wrap the subscriber's writer in [`BufferedMakeWriter`] to enable it. The writer only
+//! sees events the subscriber already enabled: set the subscriber's max level (or
+//! filter) to the most verbose level you want to keep, e.g. `DEBUG`. Outside buffered
+//! call trees those events are written as usual, so combine it with a filter such as
+//! `filter::VerboseFilter` if they should only appear on failure.
+//!
+//! ```no_run
+//! use log_args_runtime::buffer::BufferedMakeWriter;
+//!
+//! tracing_subscriber::fmt()
+//!     .json()
+//!     .with_max_level(tracing::Level::DEBUG)
+//!     .with_writer(BufferedMakeWriter::new(std::io::stdout))
+//!     .init();
+//! ```
+
+use crate::FrameState;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Events kept per call tree before the oldest are dropped
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Destination of a buffered line, captured when the line was formatted
+type Sink = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// Ring buffer of one buffered call tree
+pub struct EventBuffer {
+    threshold: Level,
+    capacity: usize,
+    lines: VecDeque<(Vec<u8>, Sink)>,
+    // Set when the owning call returns; tasks still holding the buffer write directly
+    closed: bool,
+}
+
+/// Buffer of a call tree, held by the context frame of the function that started it
+pub(crate) type SharedBuffer = Arc<Mutex<EventBuffer>>;
+
+fn lock(buffer: &SharedBuffer) -> MutexGuard<'_, EventBuffer> {
+    buffer
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl EventBuffer {
+    fn push(&mut self, line: Vec<u8>, sink: Sink) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back((line, sink));
+    }
+}
+
+// Buffer of the innermost buffered call tree, unless its owner already returned
+fn current() -> Option<SharedBuffer> {
+    crate::find_state(|state| state.buffer.clone()).filter(|buffer| !lock(buffer).closed)
+}
+
+/// Start buffering events less severe than `threshold` for the current call tree.
+///
+/// Nested calls share the outermost buffer; only the outermost guard discards it.
+pub fn start(threshold: Level, capacity: usize) -> BufferGuard {
+    let owner = match current() {
+        Some(_) => None,
+        None => {
+            let buffer = Arc::new(Mutex::new(EventBuffer {
+                threshold,
+                capacity,
+                lines: VecDeque::new(),
+                closed: false,
+            }));
+            let frame = crate::push_state(FrameState {
+                buffer: Some(Arc::clone(&buffer)),
+                ..FrameState::default()
+            });
+            Some((buffer, frame))
+        }
+    };
+    BufferGuard {
+        owner,
+        failed: false,
+    }
+}
+
+/// Whether the current call tree is buffered
+pub fn is_active() -> bool {
+    current().is_some()
+}
+
+/// Write every buffered line to its original destination, oldest first
+pub fn flush() {
+    if let Some(buffer) = current() {
+        flush_buffer(&buffer);
+    }
+}
+
+fn flush_buffer(buffer: &SharedBuffer) {
+    let lines = std::mem::take(&mut lock(buffer).lines);
+    for (line, sink) in lines {
+        sink(&line);
+    }
+}
+
+/// Drop every buffered line without writing it
+pub fn discard() {
+    if let Some(buffer) = current() {
+        lock(&buffer).lines.clear();
+    }
+}
+
+/// Guard returned by [`start`]; flushes on failure and releases the buffer on drop
+pub struct BufferGuard {
+    // Set for the outermost guard: its buffer and the frame holding it
+    owner: Option<(SharedBuffer, crate::ContextGuard)>,
+    failed: bool,
+}
+
+impl BufferGuard {
+    /// Mark the call as failed, e.g. because it returned `Err`
+    pub fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+}
+
+impl Drop for BufferGuard {
+    fn drop(&mut self) {
+        if self.failed || std::thread::panicking() {
+            flush();
+        }
+        if let Some((buffer, _frame)) = &self.owner {
+            let mut buffer = lock(buffer);
+            buffer.closed = true;
+            buffer.lines.clear();
+        }
+    }
+}
+
+/// What to do with an event of `level` given the active buffer
+enum Route {
+    Buffer(SharedBuffer),
+    FlushThenWrite(SharedBuffer),
+    Write,
+}
+
+fn route(level: &Level) -> Route {
+    match current() {
+        Some(buffer) if *level == Level::ERROR => Route::FlushThenWrite(buffer),
+        Some(buffer) if *level > lock(&buffer).threshold => Route::Buffer(buffer),
+        _ => Route::Write,
+    }
+}
+
+/// `MakeWriter` that diverts events inside a buffered call tree into its ring buffer.
+///
+/// Only events enabled by the subscriber reach the writer, so its max level must include
+/// the buffered levels; see the [module docs](self).
+pub struct BufferedMakeWriter<M> {
+    inner: Arc<M>,
+    sink: Sink,
+}
+
+impl<M> BufferedMakeWriter<M>
+where
+    M: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    pub fn new(inner: M) -> Self {
+        let inner = Arc::new(inner);
+        let target = Arc::clone(&inner);
+        let sink: Sink = Arc::new(move |line: &[u8]| {
+            let _ = target.make_writer().write_all(line);
+        });
+        Self { inner, sink }
+    }
+}
+
+impl<'a, M> MakeWriter<'a> for BufferedMakeWriter<M>
+where
+    M: MakeWriter<'a> + 'a,
+{
+    type Writer = BufferedWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        BufferedWriter::Direct(self.inner.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        if meta.is_event() {
+            match route(meta.level()) {
+                Route::Buffer(buffer) => {
+                    return BufferedWriter::Buffered {
+                        line: Vec::new(),
+                        sink: Arc::clone(&self.sink),
+                        buffer,
+                    }
+                }
+                Route::FlushThenWrite(buffer) => flush_buffer(&buffer),
+                Route::Write => {}
+            }
+        }
+        BufferedWriter::Direct(self.inner.make_writer_for(meta))
+    }
+}
+
+/// Writer produced by [`BufferedMakeWriter`]
+pub enum BufferedWriter<W> {
+    /// Writes straight through to the wrapped writer
+    Direct(W),
+    /// Collects one formatted event and stores it in the buffer on drop
+    Buffered {
+        line: Vec<u8>,
+        sink: Sink,
+        buffer: SharedBuffer,
+    },
+}
+
+impl<W: Write> Write for BufferedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            BufferedWriter::Direct(writer) => writer.write(buf),
+            BufferedWriter::Buffered { line, .. } => {
+                line.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            BufferedWriter::Direct(writer) => writer.flush(),
+            BufferedWriter::Buffered { .. } => Ok(()),
+        }
+    }
+}
+
+impl<W> Drop for BufferedWriter<W> {
+    fn drop(&mut self) {
+        if let BufferedWriter::Buffered { line, sink, buffer } = self {
+            let line = std::mem::take(line);
+            let mut buffer = lock(buffer);
+            if buffer.closed {
+                // The call tree ended while the event was being formatted
+                drop(buffer);
+                sink(&line);
+            } else {
+                buffer.push(line, Arc::clone(sink));
+            }
+        }
+    }
+}
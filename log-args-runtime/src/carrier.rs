@@ -0,0 +1,108 @@
+//! Transport-agnostic context carriers.
+//!
+//! A [`ContextCarrier`] is anything that can hold string key/value headers: HTTP header
+//! maps, Kafka record headers, AMQP properties, or a plain `HashMap` in tests. The
+//! [`propagation`](crate::propagation) rules (allowlist, size limits, W3C encoding) are
+//! written once against this trait, so every transport shares them.
+//!
+//! Implementations are provided for:
+//! - `HashMap<String, String>`
+//! - `Vec<(K, V)>` key/value lists such as Kafka's `Vec<(String, Vec<u8>)>`
+//! - `http::HeaderMap` (requires the `http` feature)
+//!
+//! Read-only header iterators can be extracted with [`extract_pairs`].
+
+use std::collections::HashMap;
+
+use crate::propagation::{self, PropagationConfig};
+use crate::{ContextFrame, ContextSnapshot};
+
+/// Storage for propagated context on the wire.
+pub trait ContextCarrier {
+    /// Returns the value stored under `key`, if any.
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Stores `value` under `key`, replacing any previous value.
+    fn set(&mut self, key: &str, value: String);
+
+    /// Writes `snapshot` into this carrier using the propagation rules in `config`.
+    fn inject(&mut self, snapshot: &ContextSnapshot, config: &PropagationConfig)
+    where
+        Self: Sized,
+    {
+        propagation::inject_snapshot(snapshot, self, config);
+    }
+
+    /// Reads a context frame from this carrier using the propagation rules in `config`.
+    fn extract(&self, config: &PropagationConfig) -> ContextFrame
+    where
+        Self: Sized,
+    {
+        propagation::extract(self, config)
+    }
+}
+
+impl ContextCarrier for HashMap<String, String> {
+    fn get(&self, key: &str) -> Option<String> {
+        // Header names are case-insensitive on every transport we care about.
+        HashMap::get(self, key).cloned().or_else(|| {
+            self.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                .map(|(_, value)| value.clone())
+        })
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+impl<K, V> ContextCarrier for Vec<(K, V)>
+where
+    K: AsRef<str> + From<String>,
+    V: AsRef<[u8]> + From<String>,
+{
+    fn get(&self, key: &str) -> Option<String> {
+        self.iter()
+            .find(|(name, _)| name.as_ref().eq_ignore_ascii_case(key))
+            .map(|(_, value)| String::from_utf8_lossy(value.as_ref()).into_owned())
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        self.retain(|(name, _)| !name.as_ref().eq_ignore_ascii_case(key));
+        self.push((K::from(key.to_string()), V::from(value)));
+    }
+}
+
+#[cfg(feature = "http")]
+impl ContextCarrier for http::HeaderMap {
+    fn get(&self, key: &str) -> Option<String> {
+        http::HeaderMap::get(self, key)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    }
+
+    fn set(&mut self, key: &str, value: String) {
+        // Keys and values that are not valid header syntax are dropped.
+        if let (Ok(name), Ok(value)) = (
+            http::header::HeaderName::from_bytes(key.as_bytes()),
+            http::header::HeaderValue::from_str(&value),
+        ) {
+            self.insert(name, value);
+        }
+    }
+}
+
+/// Extracts a context frame from a read-only iterator of header pairs.
+pub fn extract_pairs<I, K, V>(pairs: I, config: &PropagationConfig) -> ContextFrame
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<str>,
+    V: AsRef<[u8]>,
+{
+    let headers: Vec<(String, Vec<u8>)> = pairs
+        .into_iter()
+        .map(|(key, value)| (key.as_ref().to_string(), value.as_ref().to_vec()))
+        .collect();
+    propagation::extract(&headers, config)
+}
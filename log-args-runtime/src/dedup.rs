@@ -0,0 +1,188 @@
+//! Deduplication of repeated log lines inside a call tree
+//!
+//! While a [`DedupGuard`] is active (`#[params(dedup)]`), events logged through
+//! [`log_with_context!`](crate::log_with_context) that share callsite, rendered message
+//! and context are collapsed: the first one is written, repeats within the window are
+//! counted, and a single "repeated N times" follow-up is written when the window ends
+//! or the function returns. The follow-up has the level and target of the event it
+//! counts. State is stored in the context frame of the function that started it, so
+//! it follows that function into async tasks and is dropped with it.
+
+use crate::FrameState;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::Level;
+
+/// Window used by `#[params(dedup)]` without an explicit duration
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Writes the "repeated N times" follow-up of an event; generated at each callsite so
+/// the follow-up keeps the event's target
+#[doc(hidden)]
+pub type Repeat = fn(Level, &'static str, &str, &crate::ContextFrame, u64);
+
+struct Repeated {
+    first_seen: Instant,
+    count: u64,
+    level: Level,
+    callsite: &'static str,
+    message: String,
+    context: crate::ContextFrame,
+    repeat: Repeat,
+}
+
+impl Repeated {
+    // The follow-up carries the context of the first occurrence, which may belong to a
+    // child frame that has already been popped
+    fn emit(&self) {
+        (self.repeat)(
+            self.level,
+            self.callsite,
+            &self.message,
+            &self.context,
+            self.count,
+        );
+    }
+}
+
+pub(crate) struct DedupScope {
+    window: Duration,
+    seen: HashMap<u64, Repeated>,
+}
+
+pub(crate) type SharedScope = Arc<Mutex<DedupScope>>;
+
+fn current() -> Option<SharedScope> {
+    crate::find_state(|state| state.dedup.clone())
+}
+
+/// Start collapsing repeated events until the guard drops
+pub fn start(window: Duration) -> DedupGuard {
+    let scope = Arc::new(Mutex::new(DedupScope {
+        window,
+        seen: HashMap::new(),
+    }));
+    DedupGuard {
+        _frame: crate::push_state(FrameState {
+            dedup: Some(scope.clone()),
+            ..FrameState::default()
+        }),
+        scope,
+    }
+}
+
+/// Whether events logged here are currently deduplicated
+pub fn is_active() -> bool {
+    current().is_some()
+}
+
+/// Decide whether an event should be written, counting it if it repeats a recent one.
+///
+/// `log_macro` is the path of the `tracing` macro used (`::tracing::warn`); its last
+/// segment gives the level of the follow-up event, which `repeat` writes.
+#[doc(hidden)]
+pub fn check(
+    callsite: &'static str,
+    log_macro: &str,
+    message: Option<&str>,
+    context: &crate::ContextFrame,
+    repeat: Repeat,
+) -> bool {
+    let Some(scope) = current() else {
+        return true;
+    };
+
+    let mut hasher = DefaultHasher::new();
+    callsite.hash(&mut hasher);
+    message.hash(&mut hasher);
+    for (key, value) in context {
+        key.hash(&mut hasher);
+        value.hash(&mut hasher);
+    }
+    let key = hasher.finish();
+
+    let mut expired = None;
+    let emit = {
+        let mut scope = scope
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let window = scope.window;
+        match scope.seen.get_mut(&key) {
+            Some(repeated) if repeated.first_seen.elapsed() < window => {
+                repeated.count += 1;
+                false
+            }
+            Some(repeated) => {
+                if repeated.count > 0 {
+                    expired = Some(Repeated {
+                        message: repeated.message.clone(),
+                        context: repeated.context.clone(),
+                        ..*repeated
+                    });
+                }
+                repeated.first_seen = Instant::now();
+                repeated.count = 0;
+                true
+            }
+            None => {
+                scope.seen.insert(
+                    key,
+                    Repeated {
+                        first_seen: Instant::now(),
+                        count: 0,
+                        level: level_of(log_macro),
+                        callsite,
+                        message: message.unwrap_or_default().to_string(),
+                        context: context.clone(),
+                        repeat,
+                    },
+                );
+                true
+            }
+        }
+    };
+
+    if let Some(repeated) = expired {
+        repeated.emit();
+    }
+    emit
+}
+
+fn level_of(log_macro: &str) -> Level {
+    match log_macro.rsplit("::").next().map(str::trim) {
+        Some("error") => Level::ERROR,
+        Some("warn") => Level::WARN,
+        Some("debug") => Level::DEBUG,
+        Some("trace") => Level::TRACE,
+        _ => Level::INFO,
+    }
+}
+
+/// Guard returned by [`start`]; writes pending follow-ups and pops the state on drop
+pub struct DedupGuard {
+    scope: SharedScope,
+    _frame: crate::ContextGuard,
+}
+
+impl Drop for DedupGuard {
+    fn drop(&mut self) {
+        let seen = {
+            let mut scope = self
+                .scope
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            std::mem::take(&mut scope.seen)
+        };
+        let mut pending: Vec<_> = seen
+            .into_values()
+            .filter(|repeated| repeated.count > 0)
+            .collect();
+        pending.sort_by_key(|repeated| repeated.first_seen);
+        for repeated in pending {
+            repeated.emit();
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Context-driven `tracing_subscriber` filters
+//!
+//! [`VerboseFilter`] wraps the normal filter (a `LevelFilter`, `EnvFilter`, ...) and
+//! additionally enables every level for call trees marked verbose, either by
+//! `#[params(verbose_if = ...)]` or by [`mark_verbose`]. The mark is stored in the call
+//! tree's context frame, so async children and spawned tasks are verbose as well:
+//!
+//! ```no_run
+//! use log_args_runtime::filter::VerboseFilter;
+//! use tracing_subscriber::filter::LevelFilter;
+//! use tracing_subscriber::prelude::*;
+//!
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer().with_filter(VerboseFilter::new(LevelFilter::INFO)))
+//!     .init();
+//! ```
+//!
+//! [`ContextFilter`] picks the level from rules on context values, such as
+//! `tenant_id=acme => debug` or `route=/health => off`:
+//!
+//! ```no_run
+//! use log_args_runtime::filter::ContextFilter;
+//! use tracing_subscriber::prelude::*;
+//!
+//! let filter: ContextFilter = "info; tenant_id=acme => debug; route=/health => off"
+//!     .parse()
+//!     .unwrap();
+//! tracing_subscriber::registry()
+//!     .with(tracing_subscriber::fmt::layer().with_filter(filter))
+//!     .init();
+//! ```
+
+use crate::FrameState;
+use std::fmt;
+use std::str::FromStr;
+use tracing::subscriber::Interest;
+use tracing::{span, Event, Metadata};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::{Context, Filter};
+
+/// Mark the current call tree as verbose until the guard drops, if `verbose` is true
+pub fn mark_verbose(verbose: bool) -> VerboseGuard {
+    let frame = verbose.then(|| {
+        crate::push_state(FrameState {
+            verbose: true,
+            ..FrameState::default()
+        })
+    });
+    VerboseGuard { _frame: frame }
+}
+
+/// Whether the current call tree is marked verbose
+pub fn is_verbose() -> bool {
+    crate::find_state(|state| state.verbose.then_some(())).is_some()
+}
+
+/// Guard returned by [`mark_verbose`] that removes the mark on drop
+pub struct VerboseGuard {
+    _frame: Option<crate::ContextGuard>,
+}
+
+/// Filter that applies `inner` normally and enables all levels inside verbose call trees.
+///
+/// Levels up to [`with_max_level`](Self::with_max_level) (default `TRACE`) can fire in
+/// any call tree, so `tracing` can no longer skip their callsites statically: a `debug!`
+/// outside verbose call trees costs one call to this filter, which checks `inner` and
+/// the verbose mark, before it is dropped. The context is still only built for enabled
+/// events. Lower the maximum to `DEBUG` to keep `trace!` callsites free.
+pub struct VerboseFilter<F> {
+    inner: F,
+    max_level: LevelFilter,
+}
+
+impl<F> VerboseFilter<F> {
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            max_level: LevelFilter::TRACE,
+        }
+    }
+
+    /// Most verbose level that verbose call trees enable; levels beyond it are left to
+    /// `inner` and keep its static per-callsite interest
+    pub fn with_max_level(mut self, max_level: LevelFilter) -> Self {
+        self.max_level = max_level;
+        self
+    }
+
+    fn elevates(&self, meta: &Metadata<'_>) -> bool {
+        self.max_level >= *meta.level()
+    }
+}
+
+impl<S, F: Filter<S>> Filter<S> for VerboseFilter<F> {
+    fn enabled(&self, meta: &Metadata<'_>, cx: &Context<'_, S>) -> bool {
+        self.inner.enabled(meta, cx) || (self.elevates(meta) && is_verbose())
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        // Callsites the inner filter rejects may still fire inside a verbose call tree
+        let interest = self.inner.callsite_enabled(meta);
+        if interest.is_always() || !self.elevates(meta) {
+            interest
+        } else {
+            Interest::sometimes()
+        }
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, cx: &Context<'_, S>) -> bool {
+        (self.elevates(event.metadata()) && is_verbose()) || self.inner.event_enabled(event, cx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner
+            .max_level_hint()
+            .map(|inner| inner.max(self.max_level))
+    }
+
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx)
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(id, values, ctx)
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx)
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx)
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx)
+    }
+}
+
+/// Environment variable read by [`ContextFilter::from_env`]
+pub const DEFAULT_FILTER_ENV: &str = "LOG_ARGS_CONTEXT_FILTER";
+
+/// A `key=value => level` rule of a [`ContextFilter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextRule {
+    pub key: String,
+    pub value: String,
+    pub level: LevelFilter,
+}
+
+impl ContextRule {
+    /// Whether a context value matches; `fields(...)` strings are stored `Debug`-quoted,
+    /// so `"acme"` matches the rule value `acme`
+    pub fn matches(&self, value: &str) -> bool {
+        let unquoted = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+        value == self.value || unquoted == self.value
+    }
+}
+
+/// Filter that chooses the maximum level from the current `log-args` context
+///
+/// Rules are checked in order against [`get_context_value`](crate::get_context_value);
+/// the first rule whose key has the given value decides the level. Events outside any
+/// matching context use the default level (`info` unless set).
+///
+/// The string form is a `;` or newline separated list of `key=value => level` rules,
+/// plus an optional bare level that sets the default:
+/// `"warn; tenant_id=acme => debug; route=/health => off"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextFilter {
+    rules: Vec<ContextRule>,
+    default: LevelFilter,
+}
+
+impl Default for ContextFilter {
+    fn default() -> Self {
+        Self::new(LevelFilter::INFO)
+    }
+}
+
+impl ContextFilter {
+    /// Filter without rules, enabling events up to `default`
+    pub fn new(default: impl Into<LevelFilter>) -> Self {
+        Self {
+            rules: Vec::new(),
+            default: default.into(),
+        }
+    }
+
+    /// Add a rule: while context `key` equals `value`, enable events up to `level`
+    pub fn rule(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+        level: impl Into<LevelFilter>,
+    ) -> Self {
+        self.rules.push(ContextRule {
+            key: key.into(),
+            value: value.into(),
+            level: level.into(),
+        });
+        self
+    }
+
+    /// Parse rules from `LOG_ARGS_CONTEXT_FILTER`; an unset variable yields the default filter
+    pub fn from_env() -> Result<Self, ParseContextFilterError> {
+        Self::from_env_var(DEFAULT_FILTER_ENV)
+    }
+
+    /// Parse rules from the environment variable `var`
+    pub fn from_env_var(var: &str) -> Result<Self, ParseContextFilterError> {
+        match std::env::var(var) {
+            Ok(rules) => rules.parse(),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn rules(&self) -> &[ContextRule] {
+        &self.rules
+    }
+
+    /// Level that applies to the current context
+    pub fn current_level(&self) -> LevelFilter {
+        self.rules
+            .iter()
+            .find(|rule| {
+                crate::get_context_value(&rule.key).map_or(false, |value| rule.matches(&value))
+            })
+            .map_or(self.default, |rule| rule.level)
+    }
+}
+
+impl FromStr for ContextFilter {
+    type Err = ParseContextFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut filter = ContextFilter::default();
+        for entry in s.split([';', '\n']).map(str::trim) {
+            if entry.is_empty() {
+                continue;
+            }
+            let Some((condition, level)) = entry.split_once("=>") else {
+                filter.default = parse_level(entry)?;
+                continue;
+            };
+            let Some((key, value)) = condition.split_once('=') else {
+                return Err(ParseContextFilterError::new(format!(
+                    "expected `key=value` before `=>` in `{entry}`"
+                )));
+            };
+            if key.trim().is_empty() {
+                return Err(ParseContextFilterError::new(format!(
+                    "empty key in `{entry}`"
+                )));
+            }
+            filter = filter.rule(key.trim(), value.trim(), parse_level(level)?);
+        }
+        Ok(filter)
+    }
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, ParseContextFilterError> {
+    level
+        .trim()
+        .parse()
+        .map_err(|_| ParseContextFilterError::new(format!("invalid level `{}`", level.trim())))
+}
+
+/// Error returned when a [`ContextFilter`] string cannot be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseContextFilterError {
+    message: String,
+}
+
+impl ParseContextFilterError {
+    fn new(message: String) -> Self {
+        Self { message }
+    }
+}
+
+impl fmt::Display for ParseContextFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid context filter: {}", self.message)
+    }
+}
+
+impl std::error::Error for ParseContextFilterError {}
+
+impl<S> Filter<S> for ContextFilter {
+    fn enabled(&self, meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
+        self.current_level() >= *meta.level()
+    }
+
+    fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
+        let max = <Self as Filter<S>>::max_level_hint(self).unwrap_or(LevelFilter::TRACE);
+        if max < *meta.level() {
+            Interest::never()
+        } else if self.rules.is_empty() {
+            Interest::always()
+        } else {
+            Interest::sometimes()
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.rules
+            .iter()
+            .map(|rule| rule.level)
+            .chain(std::iter::once(self.default))
+            .max()
+    }
+}
@@ -1,16 +1,29 @@
 //! log-args-runtime
 //!
 //! Runtime support for the `log_args` procedural macros. This crate provides:
-//! - Context storage and helpers to push/pop context across sync and async boundaries
+//! - Context storage and helpers to push/pop context across sync and async boundaries,
+//!   with [`with_context`] keeping a task's frames with the task
 //! - Logging macros (`info!`, `warn!`, `error!`, `debug!`, `trace!`) that automatically
 //!   merge inherited context into your events
 //! - `log_with_context!` which enriches an underlying `tracing` macro
+//! - `record!` which adds fields to the current function's context once they are known
+//! - [`propagation`]: W3C `baggage`/`traceparent` inject and extract across process boundaries
+//! - [`carrier`]: the [`ContextCarrier`] trait that lets any transport's headers carry context
+//! - [`process`]: hand context to child processes through `LOG_ARGS_CTX_*` environment variables
+//! - [`wide_event`]: one summary event per call with duration and outcome
+//! - [`audit`]: audit trail of who did what, routed to its own sink
+//! - [`buffer`]: hold a call tree's debug events and write them only if the call fails
+//! - [`filter`]: `tracing_subscriber` filters driven by the current context
+//! - [`sampling`]: per-call sampling and rate limiting
+//! - [`dedup`]: collapse repeated log lines into one "repeated N times" follow-up
+//! - `metrics` (feature `metrics`): call, error, in-flight and duration metrics per function
 //!
 //! Feature flags
 //! - `with_context` (off by default): When enabled, the runtime includes a `context` field
 //!   (debug-formatted map) in each log when there is context available. Configure your
 //!   `tracing-subscriber` JSON formatter with `.flatten_event(true)` to surface the fields
 //!   at the top level in JSON output.
+//! - `metrics` (off by default): enables the `metrics` module used by `#[params(metrics)]`.
 //!
 //! Quick start
 //! ```no_run
@@ -26,28 +39,104 @@
 //! }
 //! ```
 //!
-use once_cell::sync::Lazy;
-use std::cell::RefCell;
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use once_cell::sync::{Lazy, OnceCell};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::task::Poll;
+
+pub mod audit;
+pub mod buffer;
+pub mod carrier;
+pub mod dedup;
+pub mod filter;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod process;
+pub mod propagation;
+pub mod sampling;
+pub mod wide_event;
+
+pub use carrier::ContextCarrier;
+pub use process::{init_from_env, CommandExt};
+
+// Non-cryptographic randomness for trace ids and sampling, without a `rand` dependency
+fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
 
 // Feature gate evaluated in this crate, not at the macro callsite.
 // Downstream crates won't see unexpected cfg values.
 pub const WITH_CONTEXT_ENABLED: bool = cfg!(feature = "with_context");
 
 // Global context store for cross-boundary persistence
-static GLOBAL_CONTEXT: Lazy<Arc<Mutex<HashMap<String, String>>>> =
-    Lazy::new(|| Arc::new(Mutex::new(HashMap::new())));
+static GLOBAL_CONTEXT: Lazy<Arc<Mutex<ContextFrame>>> =
+    Lazy::new(|| Arc::new(Mutex::new(ContextFrame::new())));
 
-/// Set global context that persists across all boundaries
+/// Set global context that persists across all boundaries.
+/// Values stay until removed; prefer [`scoped_global_context`] for temporary overrides.
+/// Events only include global fields outside a call tree; see [`get_context`].
 pub fn set_global_context(key: &str, value: &str) {
     if let Ok(mut global) = GLOBAL_CONTEXT.lock() {
         global.insert(key.to_string(), value.to_string());
     }
 }
 
+/// Remove a key from the global context, returning its previous value
+pub fn remove_global_context(key: &str) -> Option<String> {
+    GLOBAL_CONTEXT
+        .lock()
+        .ok()
+        .and_then(|mut global| global.shift_remove(key))
+}
+
+/// Remove every key from the global context
+pub fn clear_global_context() {
+    if let Ok(mut global) = GLOBAL_CONTEXT.lock() {
+        global.clear();
+    }
+}
+
+/// Set a global context value until the returned guard drops, then restore the
+/// previous value (or remove the key if it was unset)
+pub fn scoped_global_context(key: &str, value: &str) -> GlobalContextGuard {
+    let previous = GLOBAL_CONTEXT
+        .lock()
+        .ok()
+        .and_then(|mut global| global.insert(key.to_string(), value.to_string()));
+    GlobalContextGuard {
+        key: key.to_string(),
+        previous,
+    }
+}
+
+/// Guard returned by [`scoped_global_context`] that restores the overridden value on drop
+pub struct GlobalContextGuard {
+    key: String,
+    previous: Option<String>,
+}
+
+impl Drop for GlobalContextGuard {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => set_global_context(&self.key, &previous),
+            None => {
+                remove_global_context(&self.key);
+            }
+        }
+    }
+}
+
 /// Get global context for cross-boundary persistence
-pub fn get_global_context() -> Option<HashMap<String, String>> {
+pub fn get_global_context() -> Option<ContextFrame> {
     if let Ok(global) = GLOBAL_CONTEXT.lock() {
         if !global.is_empty() {
             return Some(global.clone());
@@ -56,10 +145,178 @@ pub fn get_global_context() -> Option<HashMap<String, String>> {
     None
 }
 
-// Thread-local storage for context stacks
+/// A single frame of context fields, as pushed by one instrumented function.
+/// Frames keep insertion order so logged context is deterministic.
+pub type ContextFrame = IndexMap<String, String>;
+
+/// Point-in-time view of the merged call-tree context
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContextSnapshot {
+    fields: ContextFrame,
+}
+
+impl ContextSnapshot {
+    /// Capture the context visible at the call site
+    ///
+    /// Only call-tree context is captured (root frame and pushed frames); static and
+    /// global context belong to the current process and are not part of a snapshot.
+    pub fn capture() -> Self {
+        Self {
+            fields: call_tree_context(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields.get(key).map(String::as_str)
+    }
+
+    pub fn fields(&self) -> &ContextFrame {
+        &self.fields
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+impl From<ContextFrame> for ContextSnapshot {
+    fn from(fields: ContextFrame) -> Self {
+        Self { fields }
+    }
+}
+
+// Process-wide root frame, seeded once at startup and shared by every thread
+static ROOT_CONTEXT: OnceCell<ContextFrame> = OnceCell::new();
+
+/// Seed the root context frame that sits beneath every thread's context stack.
+/// Returns `false` if the root frame was already seeded.
+pub fn seed_root_context(frame: ContextFrame) -> bool {
+    ROOT_CONTEXT.set(frame).is_ok()
+}
+
+fn root_context() -> Option<&'static ContextFrame> {
+    ROOT_CONTEXT.get()
+}
+
+/// Prefix of environment variables read by [`init_static_context_from_env`]
+pub const STATIC_ENV_PREFIX: &str = "LOG_ARGS_STATIC_";
+
+// Process-wide fields such as `service` or `version`, immutable once initialized
+static STATIC_CONTEXT: OnceCell<ContextFrame> = OnceCell::new();
+
+/// Declare process-wide fields (`service`, `version`, `env`, `region`, ...) that every
+/// log event includes. Call once at startup; returns `false` if static context was
+/// already initialized, in which case `fields` are discarded.
+pub fn init_static_context<I, K, V>(fields: I) -> bool
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: Into<String>,
+    V: Into<String>,
+{
+    let frame = fields
+        .into_iter()
+        .map(|(key, value)| (key.into(), value.into()))
+        .collect();
+    STATIC_CONTEXT.set(frame).is_ok()
+}
+
+/// Initialize static context from `LOG_ARGS_STATIC_*` environment variables,
+/// e.g. `LOG_ARGS_STATIC_SERVICE=billing` becomes `service=billing`
+pub fn init_static_context_from_env() -> bool {
+    init_static_context(static_context_from_env())
+}
+
+/// Read `LOG_ARGS_STATIC_*` environment variables as lowercase static context fields,
+/// for combining with fields declared in code before calling [`init_static_context`]
+pub fn static_context_from_env() -> ContextFrame {
+    process::context_from_env(STATIC_ENV_PREFIX)
+        .into_iter()
+        .map(|(key, value)| (key.to_ascii_lowercase(), value))
+        .collect()
+}
+
+/// Get the static context declared at startup (empty if never initialized)
+pub fn static_context() -> &'static ContextFrame {
+    static EMPTY: Lazy<ContextFrame> = Lazy::new(ContextFrame::new);
+    STATIC_CONTEXT.get().unwrap_or(&EMPTY)
+}
+
+/// An entry of a context stack: the fields a function pushed, or call-tree state such
+/// as an event buffer that belongs to the frame but is never logged
+#[derive(Clone, Default)]
+struct Frame {
+    fields: ContextFrame,
+    state: Option<FrameState>,
+}
+
+impl Frame {
+    fn fields(fields: ContextFrame) -> Self {
+        Self {
+            fields,
+            state: None,
+        }
+    }
+}
+
+/// Call-tree state carried by a frame, so it follows the context into async tasks
+#[derive(Clone, Default)]
+pub(crate) struct FrameState {
+    pub(crate) buffer: Option<buffer::SharedBuffer>,
+    pub(crate) dedup: Option<dedup::SharedScope>,
+    pub(crate) sampling: Option<sampling::Decision>,
+    pub(crate) verbose: bool,
+}
+
+// Thread-local storage for context stacks; a `ContextFuture` swaps in its task's stacks
+// while it is polled
 thread_local! {
-    static CONTEXT_STACK: RefCell<Vec<HashMap<String, String>>> = const { RefCell::new(Vec::new()) };
-    static ASYNC_CONTEXT_STACK: RefCell<Vec<HashMap<String, String>>> = const { RefCell::new(Vec::new()) };
+    static CONTEXT_STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+    static ASYNC_CONTEXT_STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+    static ISOLATED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether the caller runs inside an isolated scope (see [`isolate`])
+pub fn is_isolated() -> bool {
+    ISOLATED.with(Cell::get)
+}
+
+/// Start a new logical unit of work that does not inherit the caller's context.
+///
+/// Until the guard drops, all parent frames are hidden, along with the root frame and
+/// the global context store. Call-tree state of the parents, such as a `buffer`, is
+/// hidden too. Static context still applies. Frames pushed inside the scope behave as
+/// usual.
+pub fn isolate() -> IsolationGuard {
+    let sync_stack = CONTEXT_STACK.with(|stack| std::mem::take(&mut *stack.borrow_mut()));
+    let async_stack = ASYNC_CONTEXT_STACK.with(|stack| std::mem::take(&mut *stack.borrow_mut()));
+    let was_isolated = ISOLATED.with(|isolated| isolated.replace(true));
+    IsolationGuard {
+        sync_stack,
+        async_stack,
+        was_isolated,
+    }
+}
+
+/// Run `f` in an isolated scope; see [`isolate`]
+pub fn isolated_scope<R>(f: impl FnOnce() -> R) -> R {
+    let _isolation_guard = isolate();
+    f()
+}
+
+/// Guard returned by [`isolate`] that restores the hidden parent frames on drop
+pub struct IsolationGuard {
+    sync_stack: Vec<Frame>,
+    async_stack: Vec<Frame>,
+    was_isolated: bool,
+}
+
+impl Drop for IsolationGuard {
+    fn drop(&mut self) {
+        CONTEXT_STACK.with(|stack| *stack.borrow_mut() = std::mem::take(&mut self.sync_stack));
+        ASYNC_CONTEXT_STACK
+            .with(|stack| *stack.borrow_mut() = std::mem::take(&mut self.async_stack));
+        ISOLATED.with(|isolated| isolated.set(self.was_isolated));
+    }
 }
 
 /// Guard for synchronous context that automatically pops on drop
@@ -74,65 +331,146 @@ impl Drop for ContextGuard {
     }
 }
 
-// Function to get a context value from the current span context
+/// Look up a single key using the merge order described in [`get_context`]
 pub fn get_context_value(key: &str) -> Option<String> {
-    // First, try async context stack
-    if let Ok(stack) = ASYNC_CONTEXT_STACK.try_with(|stack| stack.borrow().clone()) {
-        for context_map in stack.iter().rev() {
-            if let Some(value) = context_map.get(key) {
-                return Some(value.clone());
+    let find = |stack: &[Frame]| {
+        stack
+            .iter()
+            .rev()
+            .find_map(|frame| frame.fields.get(key).cloned())
+    };
+
+    CONTEXT_STACK
+        .with(|stack| find(&stack.borrow()))
+        .or_else(|| {
+            ASYNC_CONTEXT_STACK
+                .try_with(|stack| find(&stack.borrow()))
+                .ok()
+                .flatten()
+        })
+        .or_else(|| {
+            if is_isolated() {
+                return None;
             }
+            root_context()
+                .and_then(|root| root.get(key).cloned())
+                .or_else(|| {
+                    if !stack_is_empty() {
+                        return None;
+                    }
+                    GLOBAL_CONTEXT.lock().ok()?.get(key).cloned()
+                })
+        })
+        .or_else(|| static_context().get(key).cloned())
+}
+
+/// Get the merged context visible at the call site.
+///
+/// Every read path (`get_context`, [`get_context_value`], [`get_inherited_fields_map`],
+/// [`get_inherited_context_string`]) uses the same merge order, lowest precedence first:
+///
+/// 1. static context from [`init_static_context`]
+/// 2. the global context store ([`set_global_context`], `#[params(global(...))]`), only
+///    while no frame on this thread has fields
+/// 3. the root frame seeded at startup (e.g. [`init_from_env`])
+/// 4. async stack frames, outermost first
+/// 5. sync stack frames, outermost first
+///
+/// A later layer overrides the value of a key set by an earlier one, but the key keeps
+/// the position where it first appeared, so parent keys always come first. Conflicts
+/// between a frame and its parents are resolved when the frame is pushed; see
+/// [`ConflictPolicy`]. Inside an [`isolate`]d scope only layers 1, 4 and 5 apply.
+///
+/// The global store is a fallback for code outside any call tree: once a function has
+/// pushed context, its events carry the call tree's fields only, so stale global keys
+/// do not show up in every request and logging does not take the global lock.
+pub fn get_context() -> ContextFrame {
+    let mut fields = static_context().clone();
+    let stack = stack_context();
+    if stack.is_empty() && !is_isolated() {
+        if let Some(global) = get_global_context() {
+            fields.extend(global);
         }
     }
+    if let Some(root) = root_context().filter(|_| !is_isolated()) {
+        fields.extend(root.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+    fields.extend(stack);
+    fields
+}
 
-    // Then try sync context stack
-    let result = CONTEXT_STACK.with(|stack| {
-        let stack = stack.borrow();
-        for context_map in stack.iter().rev() {
-            if let Some(value) = context_map.get(key) {
-                return Some(value.clone());
-            }
-        }
-        None
-    });
+// Whether no frame on this thread has fields, i.e. there is no inherited context
+fn stack_is_empty() -> bool {
+    let empty = |stack: &[Frame]| stack.iter().all(|frame| frame.fields.is_empty());
+    CONTEXT_STACK.with(|stack| empty(&stack.borrow()))
+        && ASYNC_CONTEXT_STACK
+            .try_with(|stack| empty(&stack.borrow()))
+            .unwrap_or(true)
+}
 
-    if result.is_some() {
-        return result;
-    }
+// Context that belongs to the call tree rather than the process: root frame and both stacks
+fn call_tree_context() -> ContextFrame {
+    let mut fields = root_context()
+        .filter(|_| !is_isolated())
+        .cloned()
+        .unwrap_or_default();
+    fields.extend(stack_context());
+    fields
+}
 
-    // Finally, try global context store for cross-boundary persistence
-    if let Ok(global) = GLOBAL_CONTEXT.lock() {
-        if let Some(value) = global.get(key) {
-            return Some(value.clone());
-        }
-    }
+// Frames pushed on this thread: async stack, then sync stack
+fn stack_context() -> ContextFrame {
+    let mut fields = get_async_context();
+    CONTEXT_STACK.with(|stack| extend_frames(&mut fields, stack.borrow().iter()));
+    fields
+}
 
-    None
+/// How a pushed frame resolves keys that its parents already set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// The child's value wins (default)
+    #[default]
+    Override,
+    /// The parent's value wins; the child's value is dropped
+    KeepParent,
+    /// The child's value wins and the parent's is kept as `parent.<key>`
+    Both,
 }
 
-/// Get current synchronous context
-#[doc(hidden)]
-pub fn get_context() -> HashMap<String, String> {
-    CONTEXT_STACK.with(|stack| {
-        stack
-            .borrow()
-            .iter()
-            .fold(HashMap::new(), |mut acc, context| {
-                acc.extend(context.clone());
-                acc
-            })
-    })
+/// Apply `policy` to `frame` against the context it is about to be pushed onto
+pub fn resolve_conflicts(frame: ContextFrame, policy: ConflictPolicy) -> ContextFrame {
+    if policy == ConflictPolicy::Override {
+        return frame;
+    }
+    let parent = get_context();
+    match policy {
+        ConflictPolicy::Override => frame,
+        ConflictPolicy::KeepParent => frame
+            .into_iter()
+            .filter(|(key, _)| !parent.contains_key(key))
+            .collect(),
+        ConflictPolicy::Both => {
+            let mut resolved = ContextFrame::with_capacity(frame.len());
+            for (key, value) in frame {
+                if let Some(parent_value) = parent.get(&key).filter(|p| **p != value) {
+                    resolved.insert(format!("parent.{key}"), parent_value.clone());
+                }
+                resolved.insert(key, value);
+            }
+            resolved
+        }
+    }
 }
 
 #[doc(hidden)]
-pub fn get_async_context() -> HashMap<String, String> {
+pub fn get_async_context() -> ContextFrame {
     ASYNC_CONTEXT_STACK
         .try_with(|stack| {
             stack
                 .borrow()
                 .iter()
-                .fold(HashMap::new(), |mut acc, context| {
-                    acc.extend(context.clone());
+                .fold(ContextFrame::new(), |mut acc, frame| {
+                    acc.extend(frame.fields.clone());
                     acc
                 })
         })
@@ -140,30 +478,103 @@ pub fn get_async_context() -> HashMap<String, String> {
 }
 
 #[doc(hidden)]
-pub fn get_current_async_stack() -> Vec<HashMap<String, String>> {
+pub fn get_current_async_stack() -> Vec<ContextFrame> {
     ASYNC_CONTEXT_STACK
-        .try_with(|stack| stack.borrow().clone())
-        .unwrap_or_else(|_| vec![HashMap::new()])
+        .try_with(|stack| {
+            stack
+                .borrow()
+                .iter()
+                .map(|frame| frame.fields.clone())
+                .collect()
+        })
+        .unwrap_or_else(|_| vec![ContextFrame::new()])
 }
 
 /// Push context for synchronous functions with span
 #[doc(hidden)]
-pub fn push_context(context: HashMap<String, String>) -> ContextGuard {
+pub fn push_context(context: ContextFrame) -> ContextGuard {
     CONTEXT_STACK.with(|stack| {
-        stack.borrow_mut().push(context);
+        stack.borrow_mut().push(Frame::fields(context));
     });
     ContextGuard
 }
 
+/// Push context for synchronous functions, resolving conflicting keys with `policy`
+#[doc(hidden)]
+pub fn push_context_with_policy(context: ContextFrame, policy: ConflictPolicy) -> ContextGuard {
+    push_context(resolve_conflicts(context, policy))
+}
+
 /// Push context for asynchronous functions with span
 #[doc(hidden)]
-pub fn push_async_context(context: HashMap<String, String>) -> AsyncContextGuard {
+pub fn push_async_context(context: ContextFrame) -> AsyncContextGuard {
     ASYNC_CONTEXT_STACK.with(|stack| {
-        stack.borrow_mut().push(context);
+        stack.borrow_mut().push(Frame::fields(context));
     });
     AsyncContextGuard
 }
 
+/// Push context for asynchronous functions, resolving conflicting keys with `policy`
+#[doc(hidden)]
+pub fn push_async_context_with_policy(
+    context: ContextFrame,
+    policy: ConflictPolicy,
+) -> AsyncContextGuard {
+    push_async_context(resolve_conflicts(context, policy))
+}
+
+/// Insert a field into the innermost context frame; see [`record!`]
+///
+/// Sync frames are preferred over async frames, matching the merge order of [`get_context`].
+/// Returns `false` when no frame is active, i.e. outside any `#[params(span)]` function.
+pub fn record_context(key: &str, value: String) -> bool {
+    // Frames that only hold call-tree state do not take fields
+    let innermost = |stack: &mut Vec<Frame>| {
+        stack
+            .iter_mut()
+            .rev()
+            .find(|frame| frame.state.is_none())
+            .map(|frame| frame.fields.insert(key.to_string(), value.clone()))
+            .is_some()
+    };
+    CONTEXT_STACK.with(|stack| innermost(&mut stack.borrow_mut()))
+        || ASYNC_CONTEXT_STACK.with(|stack| innermost(&mut stack.borrow_mut()))
+}
+
+/// Record fields into the current function's context frame mid-function
+///
+/// Later log calls and child functions see the recorded fields until the frame is popped.
+/// Values use `Display`; prefix with `?` to use `Debug`, like `tracing` fields.
+///
+/// ```
+/// // Inside a `#[params(span)]` function the frame is already pushed
+/// let _frame = log_args_runtime::push_context(Default::default());
+///
+/// let user_id = 42;
+/// log_args_runtime::record!(user_id = user_id, email = ?"a@example.com");
+/// assert_eq!(log_args_runtime::get_context()["user_id"], "42");
+/// assert_eq!(log_args_runtime::get_context()["email"], "\"a@example.com\"");
+/// ```
+#[macro_export]
+macro_rules! record {
+    () => {};
+    ($key:ident = ?$value:expr $(, $($rest:tt)*)?) => {{
+        $crate::record_context(stringify!($key), format!("{:?}", $value));
+        $($crate::record!($($rest)*);)?
+    }};
+    ($key:ident = $value:expr $(, $($rest:tt)*)?) => {{
+        $crate::record_context(stringify!($key), format!("{}", $value));
+        $($crate::record!($($rest)*);)?
+    }};
+}
+
+/// Run a function body as a closure so its return value can be inspected even when the
+/// body returns early
+#[doc(hidden)]
+pub fn call_body<R, F: FnOnce() -> R>(body: F) -> R {
+    body()
+}
+
 /// Guard for async context that automatically pops on drop
 pub struct AsyncContextGuard;
 
@@ -175,57 +586,350 @@ impl Drop for AsyncContextGuard {
     }
 }
 
+/// Push a frame that holds call-tree state but no fields; pops on drop
+pub(crate) fn push_state(state: FrameState) -> ContextGuard {
+    CONTEXT_STACK.with(|stack| {
+        stack.borrow_mut().push(Frame {
+            fields: ContextFrame::new(),
+            state: Some(state),
+        });
+    });
+    ContextGuard
+}
+
+/// Innermost call-tree state for which `f` returns a value, in the order of [`record_context`]
+pub(crate) fn find_state<T>(f: impl Fn(&FrameState) -> Option<T>) -> Option<T> {
+    let find = |stack: &[Frame]| {
+        stack
+            .iter()
+            .rev()
+            .filter_map(|frame| frame.state.as_ref())
+            .find_map(&f)
+    };
+    CONTEXT_STACK
+        .try_with(|stack| find(&stack.borrow()))
+        .ok()
+        .flatten()
+        .or_else(|| {
+            ASYNC_CONTEXT_STACK
+                .try_with(|stack| find(&stack.borrow()))
+                .ok()
+                .flatten()
+        })
+}
+
+/// Context stacks of one task, swapped in while its future is polled
+#[derive(Clone, Default)]
+struct TaskContext {
+    sync_stack: Vec<Frame>,
+    async_stack: Vec<Frame>,
+    isolated: bool,
+}
+
+impl TaskContext {
+    fn current() -> Self {
+        Self {
+            sync_stack: CONTEXT_STACK.with(|stack| stack.borrow().clone()),
+            async_stack: ASYNC_CONTEXT_STACK.with(|stack| stack.borrow().clone()),
+            isolated: is_isolated(),
+        }
+    }
+
+    // Exchange with the thread's context; swapping again restores it
+    fn swap(&mut self) {
+        let _ = CONTEXT_STACK
+            .try_with(|stack| std::mem::swap(&mut *stack.borrow_mut(), &mut self.sync_stack));
+        let _ = ASYNC_CONTEXT_STACK
+            .try_with(|stack| std::mem::swap(&mut *stack.borrow_mut(), &mut self.async_stack));
+        let _ = ISOLATED.try_with(|isolated| self.isolated = isolated.replace(self.isolated));
+    }
+
+    fn enter(&mut self) -> EnteredTask<'_> {
+        self.swap();
+        EnteredTask(self)
+    }
+}
+
+// Restores the thread's context on drop, also when polling panics
+struct EnteredTask<'a>(&'a mut TaskContext);
+
+impl Drop for EnteredTask<'_> {
+    fn drop(&mut self) {
+        self.0.swap();
+    }
+}
+
+/// Run `future` with the context visible here, wherever and whenever it is polled.
+///
+/// The future gets its own copy of the current frames, including call-tree state such as
+/// an event buffer. Frames it pushes stay with it across `.await` points instead of
+/// showing up in other tasks polled on the same thread. `#[params]` wraps the futures
+/// passed to `spawn(...)` in it, and the bodies of async functions that push frames or
+/// call-tree state (`span`, `buffer`, `dedup`, `sample`, ...).
+///
+/// Each call copies the frames once, so the cost grows with the depth of the call tree.
+pub fn with_context<F: Future>(future: F) -> ContextFuture<F> {
+    ContextFuture {
+        inner: Some(Box::pin(future)),
+        context: TaskContext::current(),
+    }
+}
+
+/// Run an async function body in its own context; see [`with_context`]
+#[doc(hidden)]
+pub fn async_body<R, F: Future<Output = R>>(body: F) -> ContextFuture<F> {
+    with_context(body)
+}
+
+/// Future returned by [`with_context`]
+pub struct ContextFuture<F> {
+    inner: Option<Pin<Box<F>>>,
+    context: TaskContext,
+}
+
+impl<F: Future> Future for ContextFuture<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let _entered = this.context.enter();
+        this.inner
+            .as_mut()
+            .expect("the inner future is only taken on drop")
+            .as_mut()
+            .poll(cx)
+    }
+}
+
+impl<F> Drop for ContextFuture<F> {
+    fn drop(&mut self) {
+        // Guards still held by the future pop their frames from its own stacks
+        if let Some(inner) = self.inner.take() {
+            let _entered = self.context.enter();
+            drop(inner);
+        }
+    }
+}
+
 #[macro_export]
 macro_rules! log_with_context {
     ($log_macro:path, $context:expr, $($args:tt)*) => {
-        {
-            let ctx = $context;
-            // Avoid cfg in macro body; use a const from this crate instead.
-            if !$crate::WITH_CONTEXT_ENABLED {
-                $log_macro!($($args)*);
-            } else {
-                // Pass the context map as a debug-formatted field.
-                // The tracing-subscriber can be configured to flatten this.
-                $log_macro!(context = ?ctx, $($args)*);
-            }
+        $crate::__log_with_context!(@prefix $log_macro, $context, {} {} {} [] $($args)*)
+    };
+}
+
+/// Implementation of [`log_with_context!`]: collects tracing's `name:`, `target:` and
+/// `parent:` prefixes, which must precede every field, then logs with the context.
+/// A repeated prefix replaces the earlier one.
+///
+/// `#[params]` passes the function's fields in `[...]` so they are placed after any
+/// prefix the log statement starts with.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_with_context {
+    (@prefix $log_macro:path, $context:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] name: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@prefix $log_macro, $context, {name: $new,} {$($target)*} {$($parent)*} [$($fields)*] $($args)*)
+    };
+    (@prefix $log_macro:path, $context:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] target: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@prefix $log_macro, $context, {$($name)*} {target: $new,} {$($parent)*} [$($fields)*] $($args)*)
+    };
+    (@prefix $log_macro:path, $context:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] parent: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@prefix $log_macro, $context, {$($name)*} {$($target)*} {parent: $new,} [$($fields)*] $($args)*)
+    };
+    // tracing accepts the prefixes only in `name:`, `target:`, `parent:` order
+    (@prefix $log_macro:path, $context:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] $($args:tt)*) => {
+        // Calls dropped by `sample` / `rate_limit` skip building the context entirely
+        if !$crate::sampling::should_emit() {
+        } else if $crate::dedup::is_active() {
+            $crate::__log_with_dedup!(@split $log_macro, $context, {$($target)*}, {$($name)* $($target)* $($parent)*} [] $($fields)* $($args)*);
+        } else {
+            $crate::__log_with_context!(@log $log_macro, {$($name)* $($target)* $($parent)*}, $context, $($fields)* $($args)*);
+        }
+    };
+    // `event!` takes its level after the prefixes; it joins the end of the prefix so the
+    // fields still follow it. Levels above `$max` (the function's `min_level`) are skipped
+    (@event $context:expr, $max:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] name: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@event $context, $max, {name: $new,} {$($target)*} {$($parent)*} [$($fields)*] $($args)*)
+    };
+    (@event $context:expr, $max:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] target: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@event $context, $max, {$($name)*} {target: $new,} {$($parent)*} [$($fields)*] $($args)*)
+    };
+    (@event $context:expr, $max:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] parent: $new:expr, $($args:tt)*) => {
+        $crate::__log_with_context!(@event $context, $max, {$($name)*} {$($target)*} {parent: $new,} [$($fields)*] $($args)*)
+    };
+    (@event $context:expr, $max:expr, {$($name:tt)*} {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] $level:expr, $($args:tt)*) => {
+        if $level <= $max && ::tracing::level_enabled!($level) {
+            $crate::__log_with_context!(@prefix ::tracing::event, $context, {$($name)*} {$($target)*} {$($parent)* $level,} [$($fields)*] $($args)*);
+        }
+    };
+    // After a prefix, tracing only reads fields whose list starts with an identifier, so
+    // an empty (unrecorded) `context` leads when the context itself is left out. Wrapper
+    // macros from `#[params(macros(...))]` may add a prefix of their own, so it always leads.
+    //
+    // `$ctx` and the fields are evaluated by `tracing` only once the level, the callsite's
+    // interest and the subscriber have enabled the event, so disabled calls build nothing
+    (@log $log_macro:path, {$($prefix:tt)*}, $ctx:expr, $($args:tt)*) => {
+        // Avoid cfg in macro body; use a const from this crate instead.
+        if !$crate::WITH_CONTEXT_ENABLED {
+            $log_macro!($($prefix)* context = ::tracing::field::Empty, $($args)*);
+        } else {
+            // Pass the context map as a debug-formatted field.
+            // The tracing-subscriber can be configured to flatten this.
+            $log_macro!($($prefix)* context = ?$ctx, $($args)*);
+        }
+    };
+}
+
+/// Dedup path of [`log_with_context!`]: splits the field prefix from the message so the
+/// message is rendered once, used for the dedup key and then logged as `"{}"`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __log_with_dedup {
+    // A literal starting an argument is the format string; the rest are its arguments
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $fmt:literal $(, $($fmt_args:tt)*)?) => {{
+        let message = format!($fmt $(, $($fmt_args)*)?);
+        $crate::__log_with_dedup!(@emit $log_macro, $context, $target, {$($prefix)*} [$($fields)*] message);
+    }};
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*]) => {
+        $crate::__log_with_dedup!(@emit $log_macro, $context, $target, {$($prefix)*} [$($fields)*]);
+    };
+    // Common `key = value` fields move in one step to stay well below the recursion limit
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $key:tt = ?$value:expr, $($rest:tt)*) => {
+        $crate::__log_with_dedup!(@split $log_macro, $context, $target, {$($prefix)*} [$($fields)* $key = ?$value,] $($rest)*);
+    };
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $key:tt = %$value:expr, $($rest:tt)*) => {
+        $crate::__log_with_dedup!(@split $log_macro, $context, $target, {$($prefix)*} [$($fields)* $key = %$value,] $($rest)*);
+    };
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $key:tt = $value:expr, $($rest:tt)*) => {
+        $crate::__log_with_dedup!(@split $log_macro, $context, $target, {$($prefix)*} [$($fields)* $key = $value,] $($rest)*);
+    };
+    (@split $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $($rest:tt)+) => {
+        $crate::__log_with_dedup!(@skip $log_macro, $context, $target, {$($prefix)*} [$($fields)*] $($rest)+);
+    };
+    // Move one field (everything up to the next top-level comma) into the prefix
+    (@skip $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] , $($rest:tt)*) => {
+        $crate::__log_with_dedup!(@split $log_macro, $context, $target, {$($prefix)*} [$($fields)* ,] $($rest)*);
+    };
+    (@skip $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $head:tt $($rest:tt)*) => {
+        $crate::__log_with_dedup!(@skip $log_macro, $context, $target, {$($prefix)*} [$($fields)* $head] $($rest)*);
+    };
+    (@skip $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*]) => {
+        $crate::__log_with_dedup!(@emit $log_macro, $context, $target, {$($prefix)*} [$($fields)*]);
+    };
+    (@emit $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*] $message:ident) => {{
+        let ctx = $context;
+        let callsite = concat!(file!(), ":", line!(), ":", column!());
+        if $crate::dedup::check(callsite, stringify!($log_macro), Some(&$message), &ctx, $crate::__log_with_dedup!(@repeat $target)) {
+            $crate::__log_with_context!(@log $log_macro, {$($prefix)*}, ctx, $($fields)* "{}", $message);
+        }
+    }};
+    (@emit $log_macro:path, $context:expr, $target:tt, {$($prefix:tt)*} [$($fields:tt)*]) => {{
+        let ctx = $context;
+        let callsite = concat!(file!(), ":", line!(), ":", column!());
+        if $crate::dedup::check(callsite, stringify!($log_macro), None, &ctx, $crate::__log_with_dedup!(@repeat $target)) {
+            $crate::__log_with_context!(@log $log_macro, {$($prefix)*}, ctx, $($fields)*);
+        }
+    }};
+    // Follow-ups are written by a closure expanded next to the log statement, so they get
+    // its `target:` prefix or, without one, the same default target (the module path)
+    (@repeat {$($target:tt)*}) => {
+        |level: ::tracing::Level, callsite: &'static str, message: &str, context: &$crate::ContextFrame, count: u64| {
+            $crate::__log_with_dedup!(@repeat_at {$($target)*} level, callsite, message, context, count,
+                ERROR WARN INFO DEBUG)
+        }
+    };
+    (@repeat_at $target:tt $level:ident, $callsite:ident, $message:ident, $context:ident, $count:ident, $($levels:ident)*) => {
+        match $level {
+            $(::tracing::Level::$levels => $crate::__log_with_dedup!(@repeat_event $target $levels, $callsite, $message, $context, $count),)*
+            _ => $crate::__log_with_dedup!(@repeat_event $target TRACE, $callsite, $message, $context, $count),
+        }
+    };
+    (@repeat_event {$($target:tt)*} $level:ident, $callsite:ident, $message:ident, $context:ident, $count:ident) => {
+        ::tracing::event!(
+            $($target)*
+            ::tracing::Level::$level,
+            context = $crate::WITH_CONTEXT_ENABLED.then(|| ::tracing::field::debug($context)),
+            callsite = $callsite,
+            repeated = $count,
+            "{} (repeated {} times)",
+            $message,
+            $count
+        )
+    };
+}
+
+/// Context-aware version of the `tracing` span macros, used by `#[params]` for `span!`,
+/// `info_span!` and friends: the context and the function's fields in `[...]` become span
+/// fields, placed after any `target:` / `parent:` prefix and the span name.
+///
+/// `@level` is for `span!`, which takes its level before the name.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __span_with_context {
+    (@$mode:ident $span_macro:path, $context:expr, {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] target: $new:expr, $($args:tt)*) => {
+        $crate::__span_with_context!(@$mode $span_macro, $context, {target: $new,} {$($parent)*} [$($fields)*] $($args)*)
+    };
+    (@$mode:ident $span_macro:path, $context:expr, {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] parent: $new:expr, $($args:tt)*) => {
+        $crate::__span_with_context!(@$mode $span_macro, $context, {$($target)*} {parent: $new,} [$($fields)*] $($args)*)
+    };
+    (@level $span_macro:path, $context:expr, {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] $level:expr, $($args:tt)*) => {
+        $crate::__span_with_context!(@name $span_macro, $context, {$($target)*} {$($parent)* $level,} [$($fields)*] $($args)*)
+    };
+    // Span fields are only evaluated when the span is enabled, so the context is too
+    (@name $span_macro:path, $context:expr, {$($target:tt)*} {$($parent:tt)*} [$($fields:tt)*] $name:expr $(, $($args:tt)*)?) => {
+        if !$crate::WITH_CONTEXT_ENABLED {
+            $span_macro!($($target)* $($parent)* $name, context = ::tracing::field::Empty, $($fields)* $($($args)*)?)
+        } else {
+            $span_macro!($($target)* $($parent)* $name, context = ?$context, $($fields)* $($($args)*)?)
         }
     };
 }
 
 /// Global context-aware logging macros that inherit parent context
 /// These can be used in any function to automatically include context from parent functions with span
+/// Levels disabled by `tracing`'s static or global max level cost a comparison and nothing else
 #[macro_export]
 macro_rules! info {
     ($($t:tt)*) => {
-        $crate::log_with_context!(::tracing::info, $crate::get_context(), $($t)*)
+        if ::tracing::level_enabled!(::tracing::Level::INFO) {
+            $crate::log_with_context!(::tracing::info, $crate::get_context(), $($t)*)
+        }
     };
 }
 
 #[macro_export]
 macro_rules! warn {
     ($($t:tt)*) => {
-        $crate::log_with_context!(::tracing::warn, $crate::get_context(), $($t)*)
+        if ::tracing::level_enabled!(::tracing::Level::WARN) {
+            $crate::log_with_context!(::tracing::warn, $crate::get_context(), $($t)*)
+        }
     };
 }
 
 #[macro_export]
 macro_rules! error {
     ($($t:tt)*) => {
-        $crate::log_with_context!(::tracing::error, $crate::get_context(), $($t)*)
+        if ::tracing::level_enabled!(::tracing::Level::ERROR) {
+            $crate::log_with_context!(::tracing::error, $crate::get_context(), $($t)*)
+        }
     };
 }
 
 #[macro_export]
 macro_rules! debug {
     ($($t:tt)*) => {
-        $crate::log_with_context!(::tracing::debug, $crate::get_context(), $($t)*)
+        if ::tracing::level_enabled!(::tracing::Level::DEBUG) {
+            $crate::log_with_context!(::tracing::debug, $crate::get_context(), $($t)*)
+        }
     };
 }
 
 #[macro_export]
 macro_rules! trace {
     ($($t:tt)*) => {
-        $crate::log_with_context!(::tracing::trace, $crate::get_context(), $($t)*)
+        if ::tracing::level_enabled!(::tracing::Level::TRACE) {
+            $crate::log_with_context!(::tracing::trace, $crate::get_context(), $($t)*)
+        }
     };
 }
 
@@ -242,122 +946,38 @@ pub fn auto_capture_context() -> ContextGuard {
     ContextGuard
 }
 
-/// Capture current context and store it globally for cross-boundary persistence
-/// This function is automatically called by the macro to ensure context is preserved
+/// Re-push the call tree's merged context as a single frame until the guard drops, so
+/// closures and tasks started in the function see one snapshot of it.
+/// Called by `#[params(auto_capture)]`; nothing is written to the global context.
 pub fn capture_context() -> ContextGuard {
-    // Merge async and sync contexts so we don't lose fields pushed to the async stack
-    let mut current_context = get_async_context();
-    let sync_ctx = get_context();
-    current_context.extend(sync_ctx);
-
-    // Store each context field globally for cross-boundary access
-    for (key, value) in &current_context {
-        set_global_context(key, value);
-    }
-
-    // Also push to context stacks for immediate access after capture
-    let _async_guard = push_async_context(current_context.clone());
-    let _sync_guard = push_context(current_context);
-
-    // Return the existing ContextGuard (empty struct)
-    ContextGuard
+    push_context(stack_context())
 }
 
 /// Get inherited context as a formatted string for automatic span propagation
-/// This function retrieves all context fields from the current span context
-/// and formats them as a string for logging
+/// This function retrieves all context fields visible at the call site, merged as
+/// described in [`get_context`], and formats them as a string for logging
 pub fn get_inherited_context_string() -> String {
-    let mut context_parts = Vec::new();
-
-    // First, try to get context from tracing span (most reliable for cross-boundary propagation)
-    let current_span = tracing::Span::current();
-    if !current_span.is_none() {
-        // Try to extract fields from the current span
-        // This works across async boundaries when spans are properly propagated
-        // Note: Direct span field extraction is complex, so we rely on other methods
-    }
-
-    // Try async context stack (most likely to have the context)
-    if let Ok(stack) = ASYNC_CONTEXT_STACK.try_with(|stack| stack.borrow().clone()) {
-        fill_context_parts(&mut context_parts, &stack);
-    }
-
-    // Also try sync context stack and merge results
-    CONTEXT_STACK.with(|stack| {
-        let stack = stack.borrow();
-        fill_context_parts(&mut context_parts, &stack);
-    });
-
-    // If still no context, try global context store (for cross-boundary persistence)
-    if context_parts.is_empty() {
-        if let Some(global_context) = get_global_context() {
-            for (key, value) in global_context {
-                if key != "function" {
-                    context_parts.push(format!("{key}={value}"));
-                }
-            }
-        }
-    }
-
-    if context_parts.is_empty() {
-        "".to_string()
-    } else {
-        context_parts.join(",")
-    }
+    get_inherited_fields_map()
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join(",")
 }
 
 /// Get inherited context fields as individual key-value pairs
-/// This function returns a HashMap of inherited context fields for dynamic field injection
-pub fn get_inherited_fields_map() -> std::collections::HashMap<String, String> {
-    let mut context_map = std::collections::HashMap::new();
-
-    // Try async context stack first
-    if let Ok(stack) = ASYNC_CONTEXT_STACK.try_with(|stack| stack.borrow().clone()) {
-        for stack_context in stack.iter().rev() {
-            for (key, value) in stack_context {
-                // Skip function name to avoid duplication
-                if key != "function" {
-                    context_map.insert(key.clone(), value.clone());
-                }
-            }
-            if !context_map.is_empty() {
-                return context_map; // Use the most recent context
-            }
-        }
-    }
-
-    // If no async context, try sync context stack
-    if context_map.is_empty() {
-        CONTEXT_STACK.with(|stack| {
-            let stack = stack.borrow();
-            for stack_context in stack.iter().rev() {
-                for (key, value) in stack_context {
-                    // Skip function name to avoid duplication
-                    if key != "function" {
-                        context_map.insert(key.clone(), value.clone());
-                    }
-                }
-                if !context_map.is_empty() {
-                    return; // Use the most recent context
-                }
-            }
-        });
-    }
-
+/// This function returns an insertion-ordered map of every inherited context field,
+/// merged as described in [`get_context`], for dynamic field injection
+pub fn get_inherited_fields_map() -> ContextFrame {
+    let mut context_map = get_context();
+    // Skip function name to avoid duplication
+    context_map.shift_remove("function");
     context_map
 }
 
-fn fill_context_parts(context_parts: &mut Vec<String>, stack: &[HashMap<String, String>]) {
-    for context_map in stack.iter().rev() {
-        for (key, value) in context_map {
-            // Skip function name to avoid duplication
-            if key != "function"
-                && !context_parts
-                    .iter()
-                    .any(|p: &String| p.starts_with(&format!("{key}=")))
-            {
-                context_parts.push(format!("{key}={value}"));
-            }
-        }
+// Extend `fields` with each frame in order; later frames override values while
+// keys keep the position where they first appeared.
+fn extend_frames<'a>(fields: &mut ContextFrame, frames: impl Iterator<Item = &'a Frame>) {
+    for frame in frames {
+        fields.extend(frame.fields.iter().map(|(k, v)| (k.clone(), v.clone())));
     }
 }
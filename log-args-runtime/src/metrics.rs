@@ -0,0 +1,129 @@
+//! RED metrics for instrumented functions through the [`metrics`](https://docs.rs/metrics) facade
+//!
+//! `#[params(metrics)]` keeps a [`FunctionMetrics`] static per function and holds a
+//! [`MetricsGuard`] for each call, which records:
+//!
+//! - [`CALLS_TOTAL`]: counter incremented when the call starts
+//! - [`ERRORS_TOTAL`]: counter incremented when the call returns `Err` or panics
+//! - [`IN_FLIGHT`]: gauge of calls currently running
+//! - [`DURATION_SECONDS`]: histogram of call durations
+//!
+//! Every metric carries a `function` label plus the labels chosen with
+//! `metrics(labels(...))`. Each label keeps at most `max_label_values` distinct values per
+//! function; later values are reported as [`OVERFLOW_LABEL_VALUE`] so a user id slipping
+//! into a label cannot blow up the series count.
+//!
+//! Install any `metrics` recorder (Prometheus exporter, StatsD, ...) to collect them.
+
+use metrics::Label;
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Counter of calls
+pub const CALLS_TOTAL: &str = "log_args_calls_total";
+/// Counter of calls that returned `Err` or panicked
+pub const ERRORS_TOTAL: &str = "log_args_errors_total";
+/// Gauge of calls in progress
+pub const IN_FLIGHT: &str = "log_args_in_flight";
+/// Histogram of call durations in seconds
+pub const DURATION_SECONDS: &str = "log_args_duration_seconds";
+
+/// Distinct values kept per label when `max_label_values` is not set
+pub const DEFAULT_MAX_LABEL_VALUES: usize = 100;
+/// Value reported for a label once its distinct values exceed the limit
+pub const OVERFLOW_LABEL_VALUE: &str = "__overflow__";
+
+/// Per-function metric labels and the cardinality guard that bounds them
+pub struct FunctionMetrics {
+    function: &'static str,
+    label_keys: &'static [&'static str],
+    max_label_values: usize,
+    seen: Mutex<Vec<HashSet<String>>>,
+}
+
+impl FunctionMetrics {
+    /// Metrics of `function` labelled by `label_keys`, each keeping at most
+    /// `max_label_values` distinct values
+    pub const fn new(
+        function: &'static str,
+        label_keys: &'static [&'static str],
+        max_label_values: usize,
+    ) -> Self {
+        Self {
+            function,
+            label_keys,
+            max_label_values,
+            seen: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Count a call with the given label values, in `label_keys` order
+    pub fn start(&'static self, label_values: Vec<String>) -> MetricsGuard {
+        let mut labels = vec![Label::new("function", self.function)];
+        if let Ok(mut seen) = self.seen.lock() {
+            seen.resize_with(self.label_keys.len(), HashSet::new);
+            for ((key, value), seen) in self
+                .label_keys
+                .iter()
+                .zip(label_values)
+                .zip(seen.iter_mut())
+            {
+                let value = if seen.contains(&value) {
+                    value
+                } else if seen.len() < self.max_label_values {
+                    seen.insert(value.clone());
+                    value
+                } else {
+                    OVERFLOW_LABEL_VALUE.to_string()
+                };
+                labels.push(Label::new(*key, value));
+            }
+        }
+
+        metrics::counter!(CALLS_TOTAL, labels.iter()).increment(1);
+        metrics::gauge!(IN_FLIGHT, labels.iter()).increment(1.0);
+        MetricsGuard {
+            labels,
+            start: Instant::now(),
+            failed: false,
+        }
+    }
+}
+
+/// Label value for a field, without the quotes `Debug` puts around strings
+#[doc(hidden)]
+pub fn label_value(value: String) -> String {
+    match value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+    {
+        Some(unquoted) => unquoted.to_string(),
+        None => value,
+    }
+}
+
+/// Guard returned by [`FunctionMetrics::start`]; records the outcome and duration on drop
+pub struct MetricsGuard {
+    labels: Vec<Label>,
+    start: Instant,
+    failed: bool,
+}
+
+impl MetricsGuard {
+    /// Mark the call as failed, e.g. because it returned `Err`
+    pub fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+}
+
+impl Drop for MetricsGuard {
+    fn drop(&mut self) {
+        metrics::gauge!(IN_FLIGHT, self.labels.iter()).decrement(1.0);
+        metrics::histogram!(DURATION_SECONDS, self.labels.iter())
+            .record(self.start.elapsed().as_secs_f64());
+        if self.failed || std::thread::panicking() {
+            metrics::counter!(ERRORS_TOTAL, self.labels.iter()).increment(1);
+        }
+    }
+}
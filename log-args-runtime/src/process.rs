@@ -0,0 +1,83 @@
+//! Context propagation to child processes through environment variables.
+//!
+//! The parent exports its current context onto a [`Command`] with
+//! [`CommandExt::with_log_context`]; each field becomes an environment variable named
+//! `LOG_ARGS_CTX_<key>`. The child calls [`init_from_env`] once at startup to seed its
+//! root context frame, so its logs carry the parent's correlation IDs.
+//!
+//! ```no_run
+//! use log_args_runtime::CommandExt;
+//! use std::process::Command;
+//!
+//! // Parent
+//! let status = Command::new("worker").with_log_context().status();
+//!
+//! // Child, first thing in `main`
+//! log_args_runtime::init_from_env();
+//! ```
+
+use std::process::Command;
+
+use crate::{ContextFrame, ContextSnapshot};
+
+/// Environment variable prefix used when none is given.
+pub const DEFAULT_ENV_PREFIX: &str = "LOG_ARGS_CTX_";
+
+/// Extension methods that export log-args context to a child process.
+pub trait CommandExt {
+    /// Exports the current context using [`DEFAULT_ENV_PREFIX`].
+    fn with_log_context(&mut self) -> &mut Self;
+
+    /// Exports the current context using a custom variable prefix.
+    fn with_log_context_prefix(&mut self, prefix: &str) -> &mut Self;
+}
+
+impl CommandExt for Command {
+    fn with_log_context(&mut self) -> &mut Self {
+        self.with_log_context_prefix(DEFAULT_ENV_PREFIX)
+    }
+
+    fn with_log_context_prefix(&mut self, prefix: &str) -> &mut Self {
+        self.envs(context_to_env(&ContextSnapshot::capture(), prefix))
+    }
+}
+
+/// Converts `snapshot` into `(name, value)` environment variable pairs.
+///
+/// Keys and values that cannot be represented in an environment variable are skipped.
+pub fn context_to_env(snapshot: &ContextSnapshot, prefix: &str) -> Vec<(String, String)> {
+    snapshot
+        .fields()
+        .iter()
+        .filter(|(key, value)| {
+            !key.is_empty() && !key.contains(['=', '\0']) && !value.contains('\0')
+        })
+        .map(|(key, value)| (format!("{prefix}{key}"), value.clone()))
+        .collect()
+}
+
+/// Collects the context fields exported by a parent process under `prefix`.
+pub fn context_from_env(prefix: &str) -> ContextFrame {
+    std::env::vars_os()
+        .filter_map(|(name, value)| {
+            let key = name.to_str()?.strip_prefix(prefix)?;
+            if key.is_empty() {
+                return None;
+            }
+            Some((key.to_string(), value.to_string_lossy().into_owned()))
+        })
+        .collect()
+}
+
+/// Seeds the root context frame from `LOG_ARGS_CTX_*` environment variables.
+///
+/// Returns the seeded frame, or `None` if the root frame was already seeded.
+pub fn init_from_env() -> Option<ContextFrame> {
+    init_from_env_with_prefix(DEFAULT_ENV_PREFIX)
+}
+
+/// Seeds the root context frame from environment variables starting with `prefix`.
+pub fn init_from_env_with_prefix(prefix: &str) -> Option<ContextFrame> {
+    let frame = context_from_env(prefix);
+    crate::seed_root_context(frame.clone()).then_some(frame)
+}
@@ -0,0 +1,305 @@
+//! W3C `baggage` / `traceparent` propagation for log-args context.
+//!
+//! [`inject`] serialises the current context into outgoing request headers and
+//! [`extract_and_push`] turns incoming headers back into a pushed context frame, so
+//! `request_id`, `tenant_id` and friends survive the hop between services.
+//!
+//! Only keys listed in the [`PropagationConfig`] allowlist cross the boundary, in
+//! either direction, and the `baggage` header is capped by entry count and byte size.
+//! Any [`ContextCarrier`] can be used, so the same rules apply to HTTP, Kafka and AMQP.
+//!
+//! ```no_run
+//! use std::collections::HashMap;
+//! use log_args_runtime::propagation::{self, PropagationConfig};
+//!
+//! let config = PropagationConfig::new(["request_id", "tenant_id"]).with_traceparent(true);
+//!
+//! // Client side
+//! let mut headers: HashMap<String, String> = HashMap::new();
+//! propagation::inject(&mut headers, &config);
+//!
+//! // Server side
+//! let _guard = propagation::extract_and_push(&headers, &config);
+//! log_args_runtime::info!("handling request");
+//! ```
+
+use crate::{random_u64, ContextCarrier, ContextFrame, ContextGuard, ContextSnapshot};
+
+/// Name of the W3C baggage header.
+pub const BAGGAGE_HEADER: &str = "baggage";
+/// Name of the W3C trace context header.
+pub const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Context key holding the 32-hex-digit trace id carried by `traceparent`.
+pub const TRACE_ID_KEY: &str = "trace_id";
+/// Context key holding the 16-hex-digit id of the current span, used as `parent-id`.
+pub const SPAN_ID_KEY: &str = "span_id";
+/// Context key that receives the caller's `parent-id` on extraction.
+pub const PARENT_ID_KEY: &str = "parent_id";
+
+/// Maximum number of list-members in a `baggage` header, per the W3C specification.
+pub const DEFAULT_MAX_ENTRIES: usize = 180;
+/// Maximum size in bytes of a `baggage` header, per the W3C specification.
+pub const DEFAULT_MAX_BYTES: usize = 8192;
+
+/// Rules for which context crosses a process boundary and how much of it.
+#[derive(Debug, Clone)]
+pub struct PropagationConfig {
+    allowed_keys: Vec<String>,
+    max_entries: usize,
+    max_bytes: usize,
+    traceparent: bool,
+}
+
+impl PropagationConfig {
+    /// Creates a config that propagates only the given context keys.
+    pub fn new<I, S>(allowed_keys: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            allowed_keys: allowed_keys.into_iter().map(Into::into).collect(),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_bytes: DEFAULT_MAX_BYTES,
+            traceparent: false,
+        }
+    }
+
+    /// Also inject and extract the `traceparent` header.
+    ///
+    /// The header is only sent for context that has a `trace_id` and a `span_id`; see
+    /// [`inject`].
+    pub fn with_traceparent(mut self, enabled: bool) -> Self {
+        self.traceparent = enabled;
+        self
+    }
+
+    /// Caps the number of `baggage` list-members.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Caps the size of the `baggage` header in bytes.
+    pub fn max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Whether `key` is on the allowlist.
+    pub fn allows(&self, key: &str) -> bool {
+        self.allowed_keys.iter().any(|allowed| allowed == key)
+    }
+}
+
+/// Writes the current context into `carrier` as `baggage` (and `traceparent` if enabled).
+///
+/// With `traceparent` enabled, a missing `trace_id` or `span_id` is generated and recorded
+/// into the current frame first, so the ids sent downstream are the ones this service
+/// logs. Outside any frame there is nowhere to record them and no `traceparent` is sent.
+pub fn inject<C: ContextCarrier + ?Sized>(carrier: &mut C, config: &PropagationConfig) {
+    if config.traceparent {
+        record_trace_ids();
+    }
+    inject_snapshot(&ContextSnapshot::capture(), carrier, config);
+}
+
+/// Writes `snapshot` into `carrier` as `baggage` (and `traceparent` if enabled).
+///
+/// `traceparent` is only written when the snapshot has a valid `trace_id` and `span_id`.
+pub fn inject_snapshot<C: ContextCarrier + ?Sized>(
+    snapshot: &ContextSnapshot,
+    carrier: &mut C,
+    config: &PropagationConfig,
+) {
+    let context = snapshot.fields();
+    let mut baggage = String::new();
+    let mut entries = 0;
+    for key in &config.allowed_keys {
+        let Some(value) = context.get(key) else {
+            continue;
+        };
+        if entries >= config.max_entries {
+            break;
+        }
+        if !is_token(key) {
+            continue;
+        }
+        let member = format!("{key}={}", percent_encode(value));
+        let separator = usize::from(!baggage.is_empty());
+        if baggage.len() + separator + member.len() > config.max_bytes {
+            continue;
+        }
+        if separator == 1 {
+            baggage.push(',');
+        }
+        baggage.push_str(&member);
+        entries += 1;
+    }
+    if !baggage.is_empty() {
+        carrier.set(BAGGAGE_HEADER, baggage);
+    }
+
+    if config.traceparent {
+        let id = |key: &str, len| context.get(key).filter(|id| is_valid_id(id, len));
+        if let (Some(trace_id), Some(parent_id)) = (id(TRACE_ID_KEY, 32), id(SPAN_ID_KEY, 16)) {
+            carrier.set(TRACEPARENT_HEADER, format!("00-{trace_id}-{parent_id}-01"));
+        }
+    }
+}
+
+// Give the current frame the trace ids it lacks, e.g. a `span_id` for a request whose
+// `trace_id` came from upstream
+fn record_trace_ids() {
+    let has_id = |key, len| crate::get_context_value(key).map_or(false, |id| is_valid_id(&id, len));
+    if !has_id(TRACE_ID_KEY, 32) {
+        crate::record_context(TRACE_ID_KEY, random_hex(2));
+    }
+    if !has_id(SPAN_ID_KEY, 16) {
+        crate::record_context(SPAN_ID_KEY, random_hex(1));
+    }
+}
+
+/// Reads allowlisted `baggage` entries (and `traceparent` if enabled) from `carrier`.
+pub fn extract<C: ContextCarrier + ?Sized>(
+    carrier: &C,
+    config: &PropagationConfig,
+) -> ContextFrame {
+    let mut context = ContextFrame::new();
+
+    if let Some(baggage) = carrier.get(BAGGAGE_HEADER) {
+        let mut consumed = 0;
+        for member in baggage.split(',').take(config.max_entries) {
+            consumed += member.len() + 1;
+            if consumed > config.max_bytes + 1 {
+                break;
+            }
+            // Properties after `;` carry no meaning for log context.
+            let pair = member.split(';').next().unwrap_or_default();
+            let Some((key, value)) = pair.split_once('=') else {
+                continue;
+            };
+            let key = key.trim();
+            if config.allows(key) {
+                context.insert(key.to_string(), percent_decode(value.trim()));
+            }
+        }
+    }
+
+    if config.traceparent {
+        if let Some((trace_id, parent_id)) = carrier
+            .get(TRACEPARENT_HEADER)
+            .as_deref()
+            .and_then(parse_traceparent)
+        {
+            context.insert(TRACE_ID_KEY.to_string(), trace_id);
+            context.insert(PARENT_ID_KEY.to_string(), parent_id);
+        }
+    }
+
+    context
+}
+
+/// Extracts context from `carrier` and pushes it as a new frame until the guard drops.
+pub fn extract_and_push<C: ContextCarrier + ?Sized>(
+    carrier: &C,
+    config: &PropagationConfig,
+) -> ContextGuard {
+    crate::push_context(extract(carrier, config))
+}
+
+fn parse_traceparent(header: &str) -> Option<(String, String)> {
+    let mut parts = header.trim().split('-');
+    let version = parts.next()?;
+    let trace_id = parts.next()?;
+    let parent_id = parts.next()?;
+    let flags = parts.next()?;
+    if version.len() != 2 || version == "ff" || flags.len() != 2 {
+        return None;
+    }
+    // Version 00 has exactly four fields; later versions may append more.
+    if version == "00" && parts.next().is_some() {
+        return None;
+    }
+    if !is_valid_id(trace_id, 32) || !is_valid_id(parent_id, 16) {
+        return None;
+    }
+    Some((trace_id.to_string(), parent_id.to_string()))
+}
+
+fn is_valid_id(id: &str, len: usize) -> bool {
+    id.len() == len
+        && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        && id.bytes().any(|b| b != b'0')
+}
+
+fn random_hex(words: usize) -> String {
+    let mut hex = String::with_capacity(words * 16);
+    for _ in 0..words {
+        hex.push_str(&format!("{:016x}", random_u64() | 1));
+    }
+    hex
+}
+
+// RFC 7230 `token`, the grammar for baggage keys.
+fn is_token(key: &str) -> bool {
+    !key.is_empty()
+        && key.bytes().all(|b| {
+            b.is_ascii_alphanumeric()
+                || matches!(
+                    b,
+                    b'!' | b'#'
+                        | b'$'
+                        | b'%'
+                        | b'&'
+                        | b'\''
+                        | b'*'
+                        | b'+'
+                        | b'-'
+                        | b'.'
+                        | b'^'
+                        | b'_'
+                        | b'`'
+                        | b'|'
+                        | b'~'
+                )
+        })
+}
+
+// Everything outside W3C `baggage-octet`, plus `%` itself, is percent-encoded.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            0x21 | 0x23..=0x24 | 0x26..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E => {
+                encoded.push(b as char)
+            }
+            _ => encoded.push_str(&format!("%{b:02X}")),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
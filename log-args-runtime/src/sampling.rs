@@ -0,0 +1,170 @@
+//! Sampling and rate limiting of instrumented calls
+//!
+//! `#[params(sample = 0.01)]` and `#[params(rate_limit = "100/s")]` decide once per call
+//! whether its events are kept, so all lines of one invocation are kept or dropped
+//! together. Decisions are stored in the call tree's context frames, so child functions,
+//! async children and spawned tasks inherit a parent's sampling decision instead of
+//! rolling their own, and any dropping decision in the call tree suppresses the events
+//! logged through [`log_with_context!`](crate::log_with_context).
+//!
+//! Events suppressed by a [`RateLimiter`] are counted and reported in one `warn` event
+//! when their window ends.
+
+use crate::FrameState;
+use once_cell::sync::Lazy;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy)]
+pub(crate) struct Decision {
+    keep: bool,
+    sampled: bool,
+    limiter: Option<&'static RateLimiter>,
+}
+
+fn push(decision: Decision) -> SamplingGuard {
+    SamplingGuard {
+        _frame: crate::push_state(FrameState {
+            sampling: Some(decision),
+            ..FrameState::default()
+        }),
+    }
+}
+
+/// Keep this call's events with probability `rate`, unless a parent call already decided
+pub fn sample(rate: impl Into<f64>) -> SamplingGuard {
+    let inherited = crate::find_state(|state| {
+        state
+            .sampling
+            .filter(|decision| decision.sampled)
+            .map(|decision| decision.keep)
+    });
+    let keep = inherited.unwrap_or_else(|| roll(rate.into()));
+    push(Decision {
+        keep,
+        sampled: true,
+        limiter: None,
+    })
+}
+
+fn roll(rate: f64) -> bool {
+    // 53 random bits give a uniform float in [0, 1)
+    let unit = (crate::random_u64() >> 11) as f64 / (1u64 << 53) as f64;
+    unit < rate
+}
+
+/// Whether an event logged now should be emitted; counts it as suppressed otherwise
+pub fn should_emit() -> bool {
+    match crate::find_state(|state| state.sampling.filter(|decision| !decision.keep)) {
+        Some(decision) => {
+            if let Some(limiter) = decision.limiter {
+                limiter.suppress();
+            }
+            false
+        }
+        None => true,
+    }
+}
+
+/// Guard returned by [`sample`] and [`RateLimiter::acquire`] that pops the decision on drop
+pub struct SamplingGuard {
+    _frame: crate::ContextGuard,
+}
+
+static EPOCH: Lazy<Instant> = Lazy::new(Instant::now);
+
+/// Fixed-window limit on the number of calls of one function whose events are kept
+///
+/// When a window suppresses events, a short-lived thread reports them as the window ends.
+pub struct RateLimiter {
+    function: &'static str,
+    limit: u64,
+    period_ms: u64,
+    window_start_ms: AtomicU64,
+    calls: AtomicU64,
+    suppressed: AtomicU64,
+    report_scheduled: AtomicBool,
+}
+
+impl RateLimiter {
+    /// Keep at most `limit` calls of `function` per `period_ms` milliseconds
+    pub const fn new(function: &'static str, limit: u64, period_ms: u64) -> Self {
+        Self {
+            function,
+            limit,
+            period_ms,
+            window_start_ms: AtomicU64::new(0),
+            calls: AtomicU64::new(0),
+            suppressed: AtomicU64::new(0),
+            report_scheduled: AtomicBool::new(false),
+        }
+    }
+
+    /// Decide whether this call's events are kept, starting a new window when due
+    pub fn acquire(&'static self) -> SamplingGuard {
+        let now = EPOCH.elapsed().as_millis() as u64;
+        let start = self.window_start_ms.load(Ordering::Relaxed);
+        if now.saturating_sub(start) >= self.period_ms
+            && self
+                .window_start_ms
+                .compare_exchange(start, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.calls.store(0, Ordering::Relaxed);
+            self.report();
+        }
+
+        let keep = self.calls.fetch_add(1, Ordering::Relaxed) < self.limit;
+        push(Decision {
+            keep,
+            sampled: false,
+            limiter: Some(self),
+        })
+    }
+
+    /// Events suppressed since the last report
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    // Count a suppressed event; the first one of a window schedules the report
+    fn suppress(&'static self) {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        if !self.report_scheduled.swap(true, Ordering::Relaxed) {
+            self.schedule_report();
+        }
+    }
+
+    /// Report when the current window ends, even if no call arrives to start the next one.
+    /// The report goes to the subscriber that was current when the event was suppressed.
+    fn schedule_report(&'static self) {
+        let dispatch = tracing::dispatcher::get_default(tracing::Dispatch::clone);
+        let window_end = self.window_start_ms.load(Ordering::Relaxed) + self.period_ms;
+        let spawned = std::thread::Builder::new()
+            .name("log-args-rate-limit".to_string())
+            .spawn(move || {
+                let now = EPOCH.elapsed().as_millis() as u64;
+                std::thread::sleep(Duration::from_millis(window_end.saturating_sub(now)));
+                self.report_scheduled.store(false, Ordering::Relaxed);
+                tracing::dispatcher::with_default(&dispatch, || self.report());
+            });
+        if spawned.is_err() {
+            // Fall back to reporting from the next window's first call
+            self.report_scheduled.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// Emit the number of suppressed events, if any, and reset the counter
+    pub fn report(&self) {
+        let suppressed = self.suppressed.swap(0, Ordering::Relaxed);
+        if suppressed > 0 {
+            tracing::warn!(
+                function = self.function,
+                suppressed,
+                limit = self.limit,
+                period_ms = self.period_ms,
+                "Rate limit suppressed events"
+            );
+        }
+    }
+}
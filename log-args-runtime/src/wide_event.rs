@@ -0,0 +1,71 @@
+//! Canonical "wide events": one summary event per function call
+//!
+//! `#[params(wide_event)]` starts a [`WideEvent`] after pushing the function's context
+//! frame. When the function exits, by normal return, early return or panic, the guard
+//! emits a single event carrying the merged context (including fields added with
+//! [`record!`](crate::record)), the call's duration and its [`Outcome`].
+
+use std::time::{Duration, Instant};
+
+/// How an instrumented function call ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Returned normally (or returned `Ok`)
+    Ok,
+    /// Returned `Err`
+    Error,
+    /// Unwound due to a panic
+    Panic,
+}
+
+impl Outcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error => "error",
+            Outcome::Panic => "panic",
+        }
+    }
+
+    /// Outcome of a finished call: `Panic` while unwinding, otherwise `Error` if `failed`
+    pub fn current(failed: bool) -> Self {
+        if std::thread::panicking() {
+            Outcome::Panic
+        } else if failed {
+            Outcome::Error
+        } else {
+            Outcome::Ok
+        }
+    }
+}
+
+/// Guard that emits the wide event on drop
+///
+/// The `emit` callback is generated at the instrumented function so the event keeps
+/// that function's module as its target.
+pub struct WideEvent {
+    start: Instant,
+    failed: bool,
+    emit: fn(Outcome, Duration),
+}
+
+impl WideEvent {
+    pub fn start(emit: fn(Outcome, Duration)) -> Self {
+        Self {
+            start: Instant::now(),
+            failed: false,
+            emit,
+        }
+    }
+
+    /// Mark the call as failed, e.g. because it returned `Err`
+    pub fn set_failed(&mut self, failed: bool) {
+        self.failed = failed;
+    }
+}
+
+impl Drop for WideEvent {
+    fn drop(&mut self) {
+        (self.emit)(Outcome::current(self.failed), self.start.elapsed());
+    }
+}
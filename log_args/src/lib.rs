@@ -187,6 +187,22 @@
 //! - `#[params(fields(param1, param2))]` - Log only specified parameters
 //! - `#[params(span(param1, param2))]` - Propagate parameters as context to child functions
 //! - `#[params(custom(key = expression))]` - Add computed custom fields
+//! - `#[params(on_conflict = keep_parent)]` - Resolve keys a parent already set (`override`, `keep_parent`, `both`)
+//! - `#[params(global(key = expression))]` - Add a custom field that is also published process-wide
+//! - `#[params(isolate)]` - Start a fresh context that hides all parent and global context
+//! - `#[params(wide_event)]` - Emit one summary event at exit with duration and outcome
+//! - `#[params(buffer)]` - Keep debug events of the call tree and write them only on failure
+//! - `#[params(verbose_if = expression)]` - Enable debug/trace for this call tree only
+//! - `#[params(sample = 0.01)]` - Keep the events of ~1% of calls, decided once per call
+//! - `#[params(rate_limit = "100/s")]` - Keep the events of at most 100 calls per second
+//! - `#[params(dedup = "30s")]` - Collapse repeated identical events within the window
+//! - `#[params(metrics(labels(tenant_id)))]` - Call, error, in-flight and duration metrics
+//! - `#[params(audit(actor = ..., action = ..., resource = ...))]` - Audit events at entry and outcome
+//! - `#[params(target = "billing", name = "ChargeCard")]` - Target and name of the function's events
+//! - `#[params(min_level = warn)]` - Silence events below a level inside the function
+//! - `#[params(macros(my_log))]` - Add fields and context to a project's wrapper macros
+//! - `#[params(rewrite_prints)]` - Log `dbg!` / `println!` as `debug!` in debug builds
+//! - `#[params(off)]` - Leave the function unchanged (the `disabled` feature does this everywhere)
 //!
 //! ## 🚫 Limitations
 //!
@@ -200,6 +216,8 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
+use std::collections::HashMap;
+use syn::ext::IdentExt;
 use syn::parse::{Parse, Parser};
 use syn::punctuated::Punctuated;
 use syn::{
@@ -210,27 +228,64 @@ use syn::{
 
 const WITH_CONTEXT_ENABLED: bool = cfg!(feature = "with_context");
 
-struct BlockRewriter;
+/// Rewrites log and span macro calls in the function body so they resolve to the macros
+/// redefined for the function
+struct BlockRewriter {
+    /// `use` aliases of rewritten macros declared in the body, e.g. `tinfo` for `tracing::info`
+    aliases: HashMap<String, syn::Path>,
+    /// Wrapper macros from `macros(...)`, as written
+    wrappers: Vec<String>,
+    /// Whether `dbg!`, `println!` and `eprintln!` are rewritten (`rewrite_prints`)
+    rewrite_prints: bool,
+}
+
+impl BlockRewriter {
+    fn new(block: &mut syn::Block, config: &AttrConfig) -> Self {
+        let mut collector = AliasCollector::default();
+        collector.visit_block_mut(block);
+        Self {
+            aliases: collector.aliases,
+            wrappers: config.macros.iter().map(path_string).collect(),
+            rewrite_prints: config.rewrite_prints,
+        }
+    }
+}
 
 impl VisitMut for BlockRewriter {
     fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        if let Some(path) = mac
+            .path
+            .get_ident()
+            .and_then(|alias| self.aliases.get(&alias.to_string()))
+        {
+            mac.path = path.clone();
+        }
+
+        #[cfg(feature = "log-macros")]
+        rewrite_log_macro(mac);
+
         let path = &mac.path;
-        if let Some(last_segment) = path.segments.last() {
-            if last_segment.ident == "info"
-                || last_segment.ident == "warn"
-                || last_segment.ident == "error"
-                || last_segment.ident == "debug"
-                || last_segment.ident == "trace"
-            {
-                if let Some(first_segment) = path.segments.first() {
-                    if first_segment.ident == "tracing" {
-                        // It's a `tracing::info!` style macro call. We need to strip `tracing::`
-                        // so it becomes `info!`, which will then be resolved to our redefined macro.
-                        let mut new_path = path.clone();
-                        new_path.segments = new_path.segments.into_iter().skip(1).collect();
-                        mac.path = new_path;
-                    }
-                }
+        if self.wrappers.contains(&path_string(path)) {
+            // Handed to `__log_args_wrapper!`, which adds the fields and context
+            let tokens = &mac.tokens;
+            mac.tokens = quote! { #path, #tokens };
+            mac.path = parse_quote! { __log_args_wrapper };
+        } else if let Some(print) = self.print_macro(path) {
+            // Handed to `__log_args_dbg!` / `__log_args_print!`, which log in debug builds;
+            // the call keeps its span so `line!()` points at it
+            if print == "dbg" {
+                mac.path = Ident::new("__log_args_dbg", print.span()).into();
+            } else {
+                let tokens = &mac.tokens;
+                mac.tokens = quote! { #print, #tokens };
+                mac.path = Ident::new("__log_args_print", print.span()).into();
+            }
+        } else if path.segments.len() > 1 && is_macro_root(&path.segments[0].ident) {
+            // It's a `tracing::info!` style macro call. We need to strip the crate path
+            // so it becomes `info!`, which will then be resolved to our redefined macro.
+            let last_segment = &path.segments[path.segments.len() - 1];
+            if is_rewritten_macro(&last_segment.ident) {
+                mac.path = last_segment.ident.clone().into();
             }
         }
 
@@ -239,6 +294,238 @@ impl VisitMut for BlockRewriter {
     }
 }
 
+impl BlockRewriter {
+    /// `dbg`, `println` or `eprintln` if `path` names one of them and `rewrite_prints` is set
+    fn print_macro(&self, path: &syn::Path) -> Option<Ident> {
+        let name = &path.segments.last()?.ident;
+        let in_std = match path.segments.len() {
+            1 => true,
+            2 => path.segments[0].ident == "std",
+            _ => false,
+        };
+        let is_print = name == "dbg" || name == "println" || name == "eprintln";
+        (self.rewrite_prints && in_std && is_print).then(|| name.clone())
+    }
+}
+
+/// Collects `use tracing::info as tinfo;` style aliases from a function body and allows
+/// the imports to go unused, since calls through them are rewritten. Plain imports such as
+/// `use tracing::info;` are renamed out of the way, as they would be ambiguous with the
+/// redefined macros.
+#[derive(Default)]
+struct AliasCollector {
+    aliases: HashMap<String, syn::Path>,
+}
+
+impl AliasCollector {
+    /// Record the aliases in `tree`, returning whether there were any
+    fn collect(&mut self, prefix: &mut Vec<Ident>, tree: &mut syn::UseTree) -> bool {
+        match tree {
+            syn::UseTree::Path(path) => {
+                prefix.push(path.ident.clone());
+                let found = self.collect(prefix, &mut path.tree);
+                prefix.pop();
+                found
+            }
+            syn::UseTree::Rename(rename) if is_imported_macro(prefix, &rename.ident) => {
+                let name = &rename.ident;
+                self.aliases.insert(
+                    rename.rename.to_string(),
+                    parse_quote! { #(#prefix::)* #name },
+                );
+                true
+            }
+            syn::UseTree::Name(use_name) if is_imported_macro(prefix, &use_name.ident) => {
+                let name = use_name.ident.clone();
+                let hidden = Ident::new(&format!("__log_args_{name}"), name.span());
+                self.aliases
+                    .insert(name.to_string(), parse_quote! { #(#prefix::)* #name });
+                *tree = if is_tracing_module(prefix, &name) {
+                    // `tracing::event` and `tracing::span` are also modules; `self` keeps
+                    // the module under its name while the macro is hidden
+                    parse_quote! { {#name as #hidden, #name::{self}} }
+                } else {
+                    parse_quote! { #name as #hidden }
+                };
+                true
+            }
+            syn::UseTree::Group(group) => {
+                // Every item is visited, even after a match
+                let mut found = false;
+                for tree in &mut group.items {
+                    found |= self.collect(prefix, tree);
+                }
+                found
+            }
+            _ => false,
+        }
+    }
+}
+
+impl VisitMut for AliasCollector {
+    fn visit_item_use_mut(&mut self, item: &mut syn::ItemUse) {
+        if self.collect(&mut vec![], &mut item.tree) {
+            item.attrs.push(parse_quote! { #[allow(unused_imports)] });
+        }
+    }
+}
+
+/// Whether `prefix::name` is a macro rewritten inside `#[params]` functions
+fn is_imported_macro(prefix: &[Ident], name: &Ident) -> bool {
+    match prefix {
+        [root] if is_macro_root(root) => is_rewritten_macro(name),
+        [root] if cfg!(feature = "log-macros") && root == "log" => {
+            let name = name.to_string();
+            LEVEL_MACROS.contains(&name.as_str()) || name == "log"
+        }
+        _ => false,
+    }
+}
+
+/// Whether `prefix::name` is `tracing::event` or `tracing::span`, which name a module too
+fn is_tracing_module(prefix: &[Ident], name: &Ident) -> bool {
+    matches!(prefix, [root] if root == "tracing") && (name == "event" || name == "span")
+}
+
+/// Crates whose macros are rewritten when called by path; `log` macros are rewritten
+/// separately since their syntax differs
+fn is_macro_root(ident: &Ident) -> bool {
+    ident == "tracing" || ident == "log_args_runtime"
+}
+
+/// Whether `ident` names a macro redefined inside `#[params]` functions
+fn is_rewritten_macro(ident: &Ident) -> bool {
+    let name = ident.to_string();
+    LEVEL_MACROS.contains(&name.as_str())
+        || SPAN_MACROS.contains(&name.as_str())
+        || name == "event"
+        || name == "span"
+}
+
+fn path_string(path: &syn::Path) -> String {
+    quote!(#path).to_string().replace(' ', "")
+}
+
+/// Rewrite a `log::info!`-style call into the `tracing` form handled by the redefined
+/// macros: key-values before `;` become fields and `log::log!(Level::Info, ...)` becomes
+/// `event!`. Calls with a `logger:` are left to `log`.
+#[cfg(feature = "log-macros")]
+fn rewrite_log_macro(mac: &mut syn::Macro) {
+    let segments = &mac.path.segments;
+    if segments.len() != 2 || segments[0].ident != "log" {
+        return;
+    }
+    let name = segments[1].ident.to_string();
+
+    let tokens: Vec<_> = mac.tokens.clone().into_iter().collect();
+    let semicolon = tokens
+        .iter()
+        .position(|token| is_punct(token, ';'))
+        .unwrap_or(tokens.len());
+    let mut head = split_args(&tokens[..semicolon]);
+    let format_args: proc_macro2::TokenStream =
+        tokens.iter().skip(semicolon + 1).cloned().collect();
+
+    if matches!(head.first(), Some(arg) if is_label(arg, "logger")) {
+        return;
+    }
+    let target = if matches!(head.first(), Some(arg) if is_label(arg, "target")) {
+        let target: proc_macro2::TokenStream = head.remove(0).into_iter().collect();
+        quote! { #target, }
+    } else {
+        quote! {}
+    };
+    let (macro_name, level) = if LEVEL_MACROS.contains(&name.as_str()) {
+        (name, quote! {})
+    } else if name == "log" {
+        match head.first().and_then(|arg| tracing_level(arg)) {
+            Some(level) => {
+                head.remove(0);
+                ("event".to_string(), quote! { ::tracing::Level::#level, })
+            }
+            None => return,
+        }
+    } else {
+        return;
+    };
+
+    let args = if semicolon < tokens.len() {
+        let fields = head.into_iter().map(log_key_value);
+        quote! { #(#fields,)* #format_args }
+    } else {
+        let args = head
+            .into_iter()
+            .map(|arg| arg.into_iter().collect::<proc_macro2::TokenStream>());
+        quote! { #(#args),* }
+    };
+
+    mac.path = Ident::new(&macro_name, proc_macro2::Span::call_site()).into();
+    mac.tokens = quote! { #target #level #args };
+}
+
+#[cfg(feature = "log-macros")]
+fn is_punct(token: &proc_macro2::TokenTree, ch: char) -> bool {
+    matches!(token, proc_macro2::TokenTree::Punct(punct) if punct.as_char() == ch)
+}
+
+/// Split macro arguments at top-level commas
+#[cfg(feature = "log-macros")]
+fn split_args(tokens: &[proc_macro2::TokenTree]) -> Vec<Vec<proc_macro2::TokenTree>> {
+    tokens
+        .split(|token| is_punct(token, ','))
+        .filter(|arg| !arg.is_empty())
+        .map(<[_]>::to_vec)
+        .collect()
+}
+
+/// Whether `arg` starts with `label:` (and not a `label::` path)
+#[cfg(feature = "log-macros")]
+fn is_label(arg: &[proc_macro2::TokenTree], label: &str) -> bool {
+    matches!(arg, [proc_macro2::TokenTree::Ident(ident), colon, next, ..]
+        if ident == label && is_punct(colon, ':') && !is_punct(next, ':'))
+}
+
+/// `tracing` level constant for a `log` level path such as `log::Level::Warn`
+#[cfg(feature = "log-macros")]
+fn tracing_level(arg: &[proc_macro2::TokenTree]) -> Option<Ident> {
+    let path_only = arg
+        .iter()
+        .all(|token| matches!(token, proc_macro2::TokenTree::Ident(_)) || is_punct(token, ':'));
+    match arg.last() {
+        Some(proc_macro2::TokenTree::Ident(level)) if path_only => {
+            let level = level.to_string().to_ascii_lowercase();
+            LEVEL_MACROS
+                .contains(&level.as_str())
+                .then(|| Ident::new(&level.to_ascii_uppercase(), proc_macro2::Span::call_site()))
+        }
+        _ => None,
+    }
+}
+
+/// `tracing` field for a `log` key-value: `key:? = value` becomes `key = ?value`, `key:%`
+/// and `key:err` use `%`, other captures use `?` and a bare `key` captures the variable
+#[cfg(feature = "log-macros")]
+fn log_key_value(arg: Vec<proc_macro2::TokenTree>) -> proc_macro2::TokenStream {
+    let mut tokens = arg.into_iter();
+    let Some(key) = tokens.next() else {
+        return quote! {};
+    };
+    let mut rest: Vec<_> = tokens.collect();
+    let mut sigil = quote! {};
+    if rest.len() >= 2 && is_punct(&rest[0], ':') {
+        sigil = match rest[1].to_string().as_str() {
+            "%" | "err" => quote! { % },
+            _ => quote! { ? },
+        };
+        rest.drain(..2);
+    }
+    let value: proc_macro2::TokenStream = match rest.split_first() {
+        Some((eq, value)) if is_punct(eq, '=') => value.iter().cloned().collect(),
+        _ => quote! { #key },
+    };
+    quote! { #key = #sigil #value }
+}
+
 struct SpawnInstrumentRewriter;
 
 impl VisitMut for SpawnInstrumentRewriter {
@@ -249,7 +536,10 @@ impl VisitMut for SpawnInstrumentRewriter {
                     if let Some(fut_arg) = expr_call.args.first_mut() {
                         let original_fut = fut_arg.clone();
                         *fut_arg = parse_quote! {
-                            ::tracing::Instrument::instrument(#original_fut, ::tracing::Span::current())
+                            ::tracing::Instrument::instrument(
+                                ::log_args_runtime::with_context(#original_fut),
+                                ::tracing::Span::current(),
+                            )
                         };
                     }
                 }
@@ -313,6 +603,14 @@ fn to_pascal_case(snake_case: &str) -> String {
 }
 
 // Get the formatted function name based on enabled features
+#[cfg(any(
+    feature = "function-names-snake",
+    feature = "function-names-camel",
+    feature = "function-names-pascal",
+    feature = "function-names-screaming",
+    feature = "function-names-kebab",
+    feature = "function-names"
+))]
 fn get_formatted_function_name(function_name: &str) -> String {
     #[cfg(feature = "function-names-camel")]
     {
@@ -580,38 +878,62 @@ fn get_formatted_function_name(function_name: &str) -> String {
 ///
 #[proc_macro_attribute]
 pub fn params(args: TokenStream, input: TokenStream) -> TokenStream {
-    let mut item = if let Ok(item_fn) = syn::parse::<syn::ItemFn>(input.clone()) {
+    params_impl(args.into(), input.into()).into()
+}
+
+/// Expansion of [`params`], on `proc_macro2` tokens so it can be unit tested
+fn params_impl(
+    args: proc_macro2::TokenStream,
+    input: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    // The `disabled` feature turns every `#[params]` into a no-op
+    if cfg!(feature = "disabled") {
+        return input;
+    }
+
+    let attrs = match Punctuated::<Attribute, Token![,]>::parse_terminated.parse2(args) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.to_compile_error(),
+    };
+    let config = AttrConfig::from_attributes(attrs);
+    if config.off {
+        return input;
+    }
+
+    let mut item = if let Ok(item_fn) = syn::parse2::<syn::ItemFn>(input.clone()) {
         FnItem::Item(item_fn)
-    } else if let Ok(impl_item_fn) = syn::parse::<syn::ImplItemFn>(input.clone()) {
+    } else if let Ok(impl_item_fn) = syn::parse2::<syn::ImplItemFn>(input.clone()) {
         FnItem::ImplItem(impl_item_fn)
     } else {
         return syn::Error::new_spanned(
-            proc_macro2::TokenStream::from(input),
+            input,
             "The #[params] attribute can only be applied to functions or methods.",
         )
-        .to_compile_error()
-        .into();
+        .to_compile_error();
     };
 
     let allow_unused_macros_attr: syn::Attribute = syn::parse_quote! { #[allow(unused_macros)] };
     item.attrs_mut().push(allow_unused_macros_attr);
 
-    let attrs = match Punctuated::<Attribute, Token![,]>::parse_terminated.parse(args) {
-        Ok(attrs) => attrs,
-        Err(e) => return e.to_compile_error().into(),
-    };
-
-    let config = AttrConfig::from_attributes(attrs);
+    if let Err(e) = check_metrics_labels(&item, &config) {
+        return e.to_compile_error();
+    }
+    if let Err(e) = check_on_conflict(&config) {
+        return e.to_compile_error();
+    }
+    if let Err(e) = check_rewrite_prints(&config) {
+        return e.to_compile_error();
+    }
     let context_fields = get_context_fields_quote(&item, &config);
 
     let is_async = item.sig().asyncness.is_some();
     let new_block_tokens = generate_new_block(&item, &config, &context_fields, is_async);
     *item.block_mut() = match syn::parse2(new_block_tokens) {
         Ok(block) => block,
-        Err(e) => return e.to_compile_error().into(),
+        Err(e) => return e.to_compile_error(),
     };
 
-    TokenStream::from(quote! { #item })
+    quote! { #item }
 }
 
 fn generate_new_block(
@@ -620,11 +942,31 @@ fn generate_new_block(
     context_fields: &[proc_macro2::TokenStream],
     is_async: bool,
 ) -> proc_macro2::TokenStream {
-    let log_redefines = get_log_redefines_with_fields(context_fields, is_async);
+    let log_redefines = get_log_redefines_with_fields(context_fields, config, is_async);
+    let global_stmts = get_global_context_stmts(config);
+    let isolate_stmt = if config.isolate {
+        quote! { let _isolation_guard = ::log_args_runtime::isolate(); }
+    } else {
+        quote! {}
+    };
+    let verbose_stmt = match &config.verbose_if {
+        Some(condition) => quote! {
+            let _verbose_guard = ::log_args_runtime::filter::mark_verbose(#condition);
+        },
+        None => quote! {},
+    };
+    let sampling_stmts = get_sampling_stmts(item, config);
+    // Started after the context push so follow-ups carry this function's context
+    let dedup_stmt = match &config.dedup {
+        Some(window) => quote! { let _dedup_guard = ::log_args_runtime::dedup::start(#window); },
+        None => quote! {},
+    };
     let original_block = item.block().clone();
     let mut transformed_block = original_block.clone();
-    BlockRewriter.visit_block_mut(&mut transformed_block);
+    BlockRewriter::new(&mut transformed_block, config).visit_block_mut(&mut transformed_block);
     SpawnInstrumentRewriter.visit_block_mut(&mut transformed_block);
+    let outcome_guards = get_outcome_guards(item, config);
+    let body = get_guarded_body(item, &transformed_block, is_async, &outcome_guards);
 
     if config.span {
         let context_map = get_context_map_for_span(item, config);
@@ -633,28 +975,399 @@ fn generate_new_block(
         } else {
             quote! {}
         };
-        let push_fn = if is_async {
-            quote! { ::log_args_runtime::push_async_context(#context_map) }
-        } else {
-            quote! { ::log_args_runtime::push_context(#context_map) }
+        let push_fn = match (&config.on_conflict, is_async) {
+            (None, true) => quote! { ::log_args_runtime::push_async_context(#context_map) },
+            (None, false) => quote! { ::log_args_runtime::push_context(#context_map) },
+            (Some(policy), true) => quote! {
+                ::log_args_runtime::push_async_context_with_policy(#context_map, #policy)
+            },
+            (Some(policy), false) => quote! {
+                ::log_args_runtime::push_context_with_policy(#context_map, #policy)
+            },
         };
 
-        quote! {
-            {
-                let _context_guard = #push_fn;
-                #auto_capture_stmt
-                #log_redefines
-                #transformed_block
-            }
-        }
+        scope_async_body(
+            item,
+            config,
+            is_async,
+            quote! {
+                {
+                    #isolate_stmt
+                    #verbose_stmt
+                    #sampling_stmts
+                    #global_stmts
+                    let _context_guard = #push_fn;
+                    #auto_capture_stmt
+                    #dedup_stmt
+                    #log_redefines
+                    #body
+                }
+            },
+        )
     } else {
-        quote! {
-            {
-                #log_redefines
-                #transformed_block
+        scope_async_body(
+            item,
+            config,
+            is_async,
+            quote! {
+                {
+                    #isolate_stmt
+                    #verbose_stmt
+                    #sampling_stmts
+                    #global_stmts
+                    #dedup_stmt
+                    #log_redefines
+                    #body
+                }
+            },
+        )
+    }
+}
+
+/// Run an async function's block in its own context, so the frames and guards it holds
+/// across `.await` belong to its task rather than to whatever the thread polls next.
+/// Bodies that hold no frames are left alone, sparing a boxed future per call.
+fn scope_async_body(
+    item: &FnItem,
+    config: &AttrConfig,
+    is_async: bool,
+    block: proc_macro2::TokenStream,
+) -> proc_macro2::TokenStream {
+    if !is_async || !config.holds_frames() {
+        return block;
+    }
+    match nameable_return_type(item) {
+        Some(ty) => quote! {
+            { ::log_args_runtime::async_body::<#ty, _>(async move #block).await }
+        },
+        None => quote! { { ::log_args_runtime::async_body(async move #block).await } },
+    }
+}
+
+/// Guards that observe how the call ends, as `(binding, constructor)` pairs in
+/// declaration order. Later guards drop first.
+fn get_outcome_guards(
+    item: &FnItem,
+    config: &AttrConfig,
+) -> Vec<(Ident, proc_macro2::TokenStream)> {
+    let mut guards = vec![];
+    if let Some(metrics) = &config.metrics {
+        guards.push((
+            parse_quote!(_metrics_guard),
+            get_metrics_guard(item, config, metrics),
+        ));
+    }
+    if let Some(buffer) = &config.buffer {
+        guards.push((parse_quote!(_buffer_guard), buffer.clone()));
+    }
+    if config.wide_event {
+        guards.push((
+            parse_quote!(_wide_event),
+            get_wide_event_guard(item, config),
+        ));
+    }
+    if let Some(audit) = &config.audit {
+        guards.push((parse_quote!(_audit_guard), get_audit_guard(item, audit)));
+    }
+    guards
+}
+
+/// Start the outcome guards and run the body. For functions returning `Result` the body
+/// is wrapped so the returned value marks the guards as failed, even on early return.
+fn get_guarded_body(
+    item: &FnItem,
+    block: &syn::Block,
+    is_async: bool,
+    guards: &[(Ident, proc_macro2::TokenStream)],
+) -> proc_macro2::TokenStream {
+    let names = guards.iter().map(|(name, _)| name);
+    let inits = guards.iter().map(|(_, init)| init);
+
+    if guards.is_empty() || !returns_result(item) {
+        return quote! {
+            #(let #names = #inits;)*
+            #block
+        };
+    }
+
+    let result = get_body_result(item, block, is_async);
+    let set_failed = guards.iter().map(|(name, _)| name);
+    quote! {
+        #(let mut #names = #inits;)*
+        #result
+        #(#set_failed.set_failed(__log_args_result.is_err());)*
+        __log_args_result
+    }
+}
+
+/// Wide event guard whose callback emits the summary event from this function's module,
+/// under the function's `target` if it has one.
+fn get_wide_event_guard(item: &FnItem, config: &AttrConfig) -> proc_macro2::TokenStream {
+    let function = item.sig().ident.to_string();
+    let message = format!("{function} finished");
+    let target = config
+        .target
+        .iter()
+        .map(|target| quote! { target: #target, });
+    let target = quote! { #(#target)* };
+    quote! {
+        ::log_args_runtime::wide_event::WideEvent::start(
+            |outcome: ::log_args_runtime::wide_event::Outcome, duration: ::std::time::Duration| {
+                let duration_ms = duration.as_secs_f64() * 1000.0;
+                if outcome == ::log_args_runtime::wide_event::Outcome::Ok {
+                    ::log_args_runtime::log_with_context!(::tracing::info, ::log_args_runtime::get_context(), #target function = #function, outcome = outcome.as_str(), duration_ms, #message);
+                } else {
+                    ::log_args_runtime::log_with_context!(::tracing::error, ::log_args_runtime::get_context(), #target function = #function, outcome = outcome.as_str(), duration_ms, #message);
+                }
+            }
+        )
+    }
+}
+
+/// Record the audit start event; the guard records the outcome when it drops.
+fn get_audit_guard(item: &FnItem, audit: &AuditConfig) -> proc_macro2::TokenStream {
+    let function = item.sig().ident.to_string();
+    let AuditConfig {
+        actor,
+        action,
+        resource,
+        fields,
+    } = audit;
+    let keys = fields.iter().map(|(key, _)| key);
+    let values = fields.iter().map(|(_, value)| value);
+    quote! {
+        ::log_args_runtime::audit::start(::log_args_runtime::audit::AuditRecord {
+            function: #function,
+            actor: ::std::string::ToString::to_string(&#actor),
+            action: ::std::string::ToString::to_string(&#action),
+            resource: ::std::string::ToString::to_string(&#resource),
+            fields: vec![#((#keys, ::std::string::ToString::to_string(&#values))),*],
+        })
+    }
+}
+
+/// Start the per-call metrics, taking label values from the matching `fields(...)`
+/// expression or, for `span(...)` fields, from the inherited context.
+fn get_metrics_guard(
+    item: &FnItem,
+    config: &AttrConfig,
+    metrics: &MetricsConfig,
+) -> proc_macro2::TokenStream {
+    let function = item.sig().ident.to_string();
+    let max_label_values = &metrics.max_label_values;
+    let keys: Vec<String> = metrics.labels.iter().map(field_key).collect();
+    let values = metrics.labels.iter().zip(&keys).map(|(label, key)| {
+        if config
+            .span_fields
+            .iter()
+            .any(|field| field_key(field) == *key)
+        {
+            quote! {
+                ::log_args_runtime::metrics::label_value(
+                    ::log_args_runtime::get_context_value(#key).unwrap_or_default()
+                )
             }
+        } else {
+            quote! { ::log_args_runtime::metrics::label_value(format!("{:?}", &#label)) }
+        }
+    });
+    quote! {
+        {
+            static __LOG_ARGS_METRICS: ::log_args_runtime::metrics::FunctionMetrics =
+                ::log_args_runtime::metrics::FunctionMetrics::new(#function, &[#(#keys),*], #max_label_values);
+            __LOG_ARGS_METRICS.start(vec![#(#values),*])
+        }
+    }
+}
+
+/// Metric labels must name a `fields(...)` or `span(...)` entry, or a parameter with `all`
+fn check_metrics_labels(item: &FnItem, config: &AttrConfig) -> syn::Result<()> {
+    let Some(metrics) = &config.metrics else {
+        return Ok(());
+    };
+    let mut allowed: Vec<String> = config
+        .fields
+        .iter()
+        .chain(&config.span_fields)
+        .map(field_key)
+        .collect();
+    if config.all_params {
+        allowed.extend(get_all_args(item).iter().map(Ident::to_string));
+    }
+    for label in &metrics.labels {
+        if !allowed.contains(&field_key(label)) {
+            return Err(syn::Error::new_spanned(
+                label,
+                "metric labels must be listed in `fields(...)` or `span(...)`",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `on_conflict` resolves keys when the function pushes its context frame, so it needs `span`
+fn check_on_conflict(config: &AttrConfig) -> syn::Result<()> {
+    match &config.on_conflict {
+        Some(policy) if !config.span => Err(syn::Error::new_spanned(
+            policy,
+            "`on_conflict` only applies to functions that push context; add `span`",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// `rewrite_prints` turns prints into `debug!` events, so a `min_level` that silences
+/// `debug` would swallow them without a trace
+fn check_rewrite_prints(config: &AttrConfig) -> syn::Result<()> {
+    let debug = LEVEL_MACROS.iter().position(|level| *level == "debug");
+    match config.min_level {
+        Some(min) if config.rewrite_prints && Some(min) < debug => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`rewrite_prints` logs prints at `debug`, which `min_level` silences; \
+             use `min_level = debug` or remove `rewrite_prints`",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Context key of a field expression, e.g. `user.id`
+fn field_key(field: &Expr) -> String {
+    quote!(#field).to_string().replace(' ', "")
+}
+
+/// Bind the body's return value to `__log_args_result`, running the body as a closure
+/// (or an awaited async block) so early `return` and `?` still yield the value here.
+fn get_body_result(item: &FnItem, block: &syn::Block, is_async: bool) -> proc_macro2::TokenStream {
+    let return_type = nameable_return_type(item);
+    match (is_async, return_type) {
+        (true, Some(ty)) => quote! { let __log_args_result: #ty = async #block.await; },
+        (true, None) => quote! { let __log_args_result = async #block.await; },
+        (false, Some(ty)) => quote! {
+            let __log_args_result = ::log_args_runtime::call_body::<#ty, _>(|| #block);
+        },
+        (false, None) => quote! {
+            let __log_args_result = ::log_args_runtime::call_body(|| #block);
+        },
+    }
+}
+
+/// Whether the declared return type is a `Result` (`Result<T, E>`, `io::Result<T>`, ...)
+fn returns_result(item: &FnItem) -> bool {
+    match &item.sig().output {
+        syn::ReturnType::Type(_, ty) => match ty.as_ref() {
+            syn::Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map_or(false, |segment| segment.ident == "Result"),
+            _ => false,
+        },
+        syn::ReturnType::Default => false,
+    }
+}
+
+/// The declared return type, unless it cannot be written in a binding (`impl Trait`)
+fn nameable_return_type(item: &FnItem) -> Option<syn::Type> {
+    struct ImplTraitFinder(bool);
+    impl VisitMut for ImplTraitFinder {
+        fn visit_type_impl_trait_mut(&mut self, _: &mut syn::TypeImplTrait) {
+            self.0 = true;
         }
     }
+
+    let syn::ReturnType::Type(_, ty) = &item.sig().output else {
+        return None;
+    };
+    let mut ty = ty.as_ref().clone();
+    let mut finder = ImplTraitFinder(false);
+    finder.visit_type_mut(&mut ty);
+    (!finder.0).then_some(ty)
+}
+
+/// Decide once per call whether its events are kept (`sample`, `rate_limit`).
+fn get_sampling_stmts(item: &FnItem, config: &AttrConfig) -> proc_macro2::TokenStream {
+    let mut stmts = vec![];
+    if let Some(rate) = &config.sample {
+        stmts.push(quote! {
+            let _sampling_guard = ::log_args_runtime::sampling::sample(#rate);
+        });
+    }
+    if let Some((limit, period_ms)) = config.rate_limit {
+        let function = item.sig().ident.to_string();
+        stmts.push(quote! {
+            static __LOG_ARGS_RATE_LIMITER: ::log_args_runtime::sampling::RateLimiter =
+                ::log_args_runtime::sampling::RateLimiter::new(#function, #limit, #period_ms);
+            let _rate_limit_guard = __LOG_ARGS_RATE_LIMITER.acquire();
+        });
+    }
+    quote! { #(#stmts)* }
+}
+
+/// Parse a rate such as `"100/s"`, `"600/m"` or `"1000/h"` into `(limit, period_ms)`
+fn parse_rate_limit(rate: &syn::LitStr) -> syn::Result<(u64, u64)> {
+    let value = rate.value();
+    let error = || {
+        syn::Error::new_spanned(
+            rate,
+            "expected a rate like \"100/s\", \"600/m\" or \"1000/h\"",
+        )
+    };
+    let (limit, unit) = value.split_once('/').ok_or_else(error)?;
+    let limit = limit.trim().parse::<u64>().map_err(|_| error())?;
+    let period_ms = match unit.trim() {
+        "s" | "sec" => 1_000,
+        "m" | "min" => 60_000,
+        "h" | "hour" => 3_600_000,
+        _ => return Err(error()),
+    };
+    Ok((limit, period_ms))
+}
+
+/// Parse a duration such as `"500ms"`, `"30s"`, `"5m"` or `"1h"` into milliseconds
+fn parse_duration_ms(duration: &syn::LitStr) -> syn::Result<u64> {
+    let value = duration.value();
+    let error = || {
+        syn::Error::new_spanned(
+            duration,
+            "expected a duration like \"500ms\", \"30s\", \"5m\" or \"1h\"",
+        )
+    };
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(error)?;
+    let amount = value[..split].parse::<u64>().map_err(|_| error())?;
+    let unit_ms = match value[split..].trim() {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return Err(error()),
+    };
+    Ok(amount * unit_ms)
+}
+
+/// Publish `global(...)` fields to the process-wide context store.
+fn get_global_context_stmts(config: &AttrConfig) -> proc_macro2::TokenStream {
+    let stmts = config.global.iter().enumerate().map(|(index, nv)| {
+        let key = &nv.path;
+        let value = &nv.value;
+        let key_str = quote!(#key).to_string().replace(' ', "");
+        let binding = global_binding(index);
+        quote! {
+            let #binding = &(#value);
+            ::log_args_runtime::set_global_context(&#key_str, &format!("{}", #binding));
+        }
+    });
+    quote! { #(#stmts)* }
+}
+
+/// Local holding the value of the `index`th `global(...)` field, so the expression is
+/// evaluated once for both the global store and the function's events
+fn global_binding(index: usize) -> Ident {
+    Ident::new(
+        &format!("__log_args_global_{index}"),
+        proc_macro2::Span::call_site(),
+    )
 }
 
 /// Represents the different attribute configurations available for the `#[params]` macro.
@@ -666,11 +1379,27 @@ fn generate_new_block(
 ///
 /// - `fields(...)` - Selectively log specific function parameters as individual fields
 /// - `custom(...)` - Add computed fields with custom expressions and metadata
+/// - `global(...)` - Like `custom(...)`, but also published to the process-wide context
 /// - `current(...)` - Log current context values (legacy/internal use)
 /// - `clone_upfront` - Clone parameters before async operations to prevent move issues
 /// - `span(...)` - Set up context propagation for child functions to inherit
 /// - `all` - Log all function parameters (use with caution in production)
 /// - `auto_capture` - Automatically capture context in closures and spawned tasks
+/// - `on_conflict = ...` - How this function's context resolves keys a parent already set
+/// - `isolate` - Hide all parent and global context for the duration of the call
+/// - `wide_event` - Emit one summary event at exit with all context, duration and outcome
+/// - `buffer(...)` - Buffer low-level events in the call tree and write them only on failure
+/// - `verbose_if = ...` - Enable debug/trace for this call tree when the condition holds
+/// - `sample = ...` - Keep the events of a random fraction of calls
+/// - `rate_limit = "..."` - Keep the events of at most N calls per period
+/// - `dedup` - Collapse repeated identical events into a "repeated N times" follow-up
+/// - `metrics` - Record call, error, in-flight and duration metrics (runtime `metrics` feature)
+/// - `audit(...)` - Record who did what to which resource on the `audit` target
+/// - `target = "..."` / `name = "..."` - Set the target and name of the function's events
+/// - `min_level = level` - Silence events below `level` inside the function
+/// - `macros(...)` - Wrapper macros that also get the function's fields and context
+/// - `rewrite_prints` - Log `dbg!`, `println!` and `eprintln!` as `debug!` in debug builds
+/// - `off` - Leave the function unchanged
 ///
 /// # Security Note
 ///
@@ -720,6 +1449,22 @@ enum Attribute {
     /// Keep expressions lightweight as they're evaluated on every log call.
     Custom(Punctuated<MetaNameValue, Token![,]>),
 
+    /// **Process-Wide Fields** - `global(field_name = expression, ...)`
+    ///
+    /// Logs the fields like `custom(...)` and also stores them in the runtime's global
+    /// context, where every thread and call tree can see them until removed.
+    /// Custom fields never leave the call tree; use `global` only for state that is
+    /// genuinely process-wide.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(global(deployment = config.deployment_id))]
+    /// fn start_server(config: Config) {
+    ///     info!("Server starting"); // Every later log can see `deployment`
+    /// }
+    /// ```
+    Global(Punctuated<MetaNameValue, Token![,]>),
+
     /// **Current Context Values** - `current(...)`
     ///
     /// Internal attribute for logging current context values. Primarily used internally
@@ -819,6 +1564,381 @@ enum Attribute {
     /// - Iterator chains with closures
     /// - Nested task spawning
     AutoCapture,
+
+    /// **Conflict Policy** - `on_conflict = override | keep_parent | both`
+    ///
+    /// Decides what happens when this function's context sets a key that a parent
+    /// function already set:
+    /// - `override` (default): the child's value wins
+    /// - `keep_parent`: the parent's value wins
+    /// - `both`: the child's value wins and the parent's is kept as `parent.<key>`
+    ///
+    /// Requires `span`, since the policy applies when the context frame is pushed.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(span, fields(user_id), on_conflict = both)]
+    /// fn impersonate(user_id: String) {
+    ///     info!("Acting as user"); // context has user_id and parent.user_id
+    /// }
+    /// ```
+    OnConflict(proc_macro2::TokenStream),
+
+    /// **Isolated Context** - `isolate`
+    ///
+    /// Starts a new logical unit of work: parent frames and the global context store are
+    /// hidden until the function returns, while static startup context still applies.
+    /// Useful for background jobs spawned from a request that must not inherit its fields.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(isolate, span, fields(job_id))]
+    /// fn run_job(job_id: u64) {
+    ///     info!("Job started"); // no request_id from the caller
+    /// }
+    /// ```
+    Isolate,
+
+    /// **Wide Event** - `wide_event`
+    ///
+    /// Emits a single summary event when the function exits, whether it returns normally,
+    /// returns early or panics. The event carries the full context, including fields added
+    /// with `log_args_runtime::record!`, plus `duration_ms` and `outcome` (`ok`, `error`
+    /// when a `Result` return is `Err`, or `panic`). Implies `span`, so recorded fields
+    /// have a frame to live in.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(wide_event, fields(order_id))]
+    /// fn checkout(order_id: u64) -> Result<Receipt, Error> {
+    ///     let user = load_user()?;
+    ///     log_args_runtime::record!(user_id = user.id);
+    ///     charge(&user)
+    /// } // one event: order_id, user_id, duration_ms, outcome
+    /// ```
+    WideEvent,
+
+    /// **Tail-Based Buffering** - `buffer` or `buffer(threshold = info, capacity = 1000)`
+    ///
+    /// Holds events less severe than `threshold` (default `info`, so `debug` and `trace`)
+    /// logged anywhere in this call tree in a ring buffer of `capacity` events. The buffer
+    /// is written out, with original timestamps, when an `error!` is logged or the function
+    /// returns `Err` or panics, and dropped when the function succeeds. Nested functions
+    /// share the outermost buffer, as do async children and spawned tasks. Requires
+    /// `log_args_runtime::buffer::BufferedMakeWriter` around the subscriber's writer, and
+    /// a subscriber max level that enables the buffered levels.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(buffer, fields(order_id))]
+    /// fn settle(order_id: u64) -> Result<(), Error> {
+    ///     debug!("Loading ledger"); // only written if settle fails
+    ///     post_entries()
+    /// }
+    /// ```
+    Buffer(proc_macro2::TokenStream),
+
+    /// **Per-Request Verbosity** - `verbose_if = expression`
+    ///
+    /// Marks this call tree as verbose while the function runs if the boolean expression
+    /// is true. With `log_args_runtime::filter::VerboseFilter` installed, `debug` and
+    /// `trace` events are enabled for marked call trees only, including their spawned
+    /// tasks.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(verbose_if = user.is_beta, fields(user.id))]
+    /// fn handle(user: User) {
+    ///     debug!("Only logged for beta users");
+    /// }
+    /// ```
+    VerboseIf(Expr),
+
+    /// **Sampling** - `sample = rate`
+    ///
+    /// Keeps the events of a call with probability `rate` (`0.0..=1.0`; integer literals
+    /// such as `1` are accepted too). The decision is made once per call, so all lines of
+    /// one invocation are kept or dropped together, and child functions and spawned tasks
+    /// inherit it.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(sample = 0.01, fields(key))]
+    /// fn cache_lookup(key: &str) {
+    ///     debug!("Cache lookup"); // logged for ~1% of calls
+    /// }
+    /// ```
+    Sample(Expr),
+
+    /// **Rate Limiting** - `rate_limit = "100/s"`
+    ///
+    /// Keeps the events of at most N calls per second (`/s`), minute (`/m`) or hour (`/h`).
+    /// Events of other calls are dropped and counted; the count is reported in one `warn`
+    /// event when the next window starts.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(rate_limit = "100/s", fields(user_id))]
+    /// fn handle_event(user_id: u64) {
+    ///     info!("Event received");
+    /// }
+    /// ```
+    RateLimit(u64, u64),
+
+    /// **Deduplication** - `dedup` or `dedup = "30s"`
+    ///
+    /// Collapses events from the same callsite with the same rendered message and context
+    /// within the window (default 10s): the first is logged, repeats are counted and one
+    /// "repeated N times" follow-up is logged when the window ends or the function returns.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(dedup = "30s", fields(host))]
+    /// fn connect_with_retry(host: &str) {
+    ///     for _ in 0..100 {
+    ///         warn!("Connection refused, retrying"); // logged once, then "repeated 99 times"
+    ///     }
+    /// }
+    /// ```
+    Dedup(proc_macro2::TokenStream),
+
+    /// **Metrics** - `metrics` or `metrics(labels(tenant_id), max_label_values = 50)`
+    ///
+    /// Records a call counter, an error counter (`Err` returns and panics), an in-flight
+    /// gauge and a duration histogram through the `metrics` facade, labelled with the
+    /// function name. `labels(...)` adds labels taken from the `fields(...)`/`span(...)`
+    /// expressions; each keeps at most `max_label_values` (default 100) distinct values.
+    /// Requires the runtime's `metrics` feature.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(metrics(labels(tenant_id)), fields(tenant_id, order_id))]
+    /// fn checkout(tenant_id: &str, order_id: u64) -> Result<(), Error> {
+    ///     charge(order_id)
+    /// }
+    /// ```
+    Metrics(MetricsConfig),
+
+    /// **Audit Trail** - `audit(actor = ..., action = ..., resource = ...)`
+    ///
+    /// Records an audit event when the call starts and one with its outcome (`ok`,
+    /// `error`, `panic`) when it ends, carrying the inherited context. `actor`, `action`
+    /// and `resource` are required; further `key = value` pairs are added to the event.
+    /// Values use `Display`. Events go to `log_args_runtime::audit`'s sink, by default
+    /// `tracing` events on the `audit` target.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(audit(actor = user.id, action = "delete", resource = doc_id), fields(doc_id))]
+    /// fn delete_document(user: &User, doc_id: u64) -> Result<(), Error> {
+    ///     store.delete(doc_id)
+    /// }
+    /// ```
+    Audit(Box<AuditConfig>),
+
+    /// **Event Target** - `target = "billing"`
+    ///
+    /// Sets the target of every event logged inside the function instead of the module
+    /// path, so subscribers can route or filter the function's events as a group.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(target = "billing", fields(order_id))]
+    /// fn charge_card(order_id: u64) {
+    ///     info!("Charging card"); // target: "billing"
+    /// }
+    /// ```
+    Target(Expr),
+
+    /// **Event Name** - `name = "ChargeCard"`
+    ///
+    /// Sets the name of every event logged inside the function.
+    Name(Expr),
+
+    /// **Minimum Level** - `min_level = warn`
+    ///
+    /// Silences `info!`, `debug!` and `trace!` (or whatever is below the given level)
+    /// inside the function, regardless of the subscriber's filter. Child functions keep
+    /// their own levels.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(min_level = warn)]
+    /// fn poll_queue() {
+    ///     debug!("Polling"); // never logged
+    ///     warn!("Queue is backing up"); // logged
+    /// }
+    /// ```
+    MinLevel(usize),
+
+    /// **Wrapper Macros** - `macros(my_log, audit_log)`
+    ///
+    /// Project-specific macros that forward their arguments to a `tracing` log macro.
+    /// Calls to them inside the function get the function's fields and context, placed
+    /// after any `target:` / `name:` / `parent:` prefix, like `info!` does.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// macro_rules! audit_log {
+    ///     ($($t:tt)*) => { tracing::info!(target: "audit_log", $($t)*) };
+    /// }
+    ///
+    /// #[params(macros(audit_log), fields(user_id))]
+    /// fn delete_account(user_id: u64) {
+    ///     audit_log!("Account deleted"); // user_id and context added
+    /// }
+    /// ```
+    Macros(Punctuated<syn::Path, Token![,]>),
+
+    /// **Print Rewriting** - `rewrite_prints`
+    ///
+    /// In debug builds, turns `dbg!`, `println!` and `eprintln!` inside the function into
+    /// `debug!` events with the function's fields and context. `dbg!` still returns its
+    /// argument. Release builds print as usual. Combining it with a `min_level` that
+    /// silences `debug` is a compile error.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// #[params(rewrite_prints, fields(order_id))]
+    /// fn total(order_id: u64, items: &[u64]) -> u64 {
+    ///     println!("{} items", items.len()); // debug event with order_id
+    ///     dbg!(items.iter().sum()) // debug event `items.iter().sum() = 42`, returns 42
+    /// }
+    /// ```
+    RewritePrints,
+
+    /// **Off** - `off`
+    ///
+    /// Leaves the function exactly as written: no macro redefinitions, no context push and
+    /// no spawn rewriting. Log macros in the body must then be imported as usual. The
+    /// `disabled` cargo feature does the same for every `#[params]`.
+    ///
+    /// # Example
+    /// ```rust,ignore
+    /// use tracing::info;
+    ///
+    /// #[params(off, fields(order_id))]
+    /// fn hot_path(order_id: u64) {
+    ///     info!("No fields or context added");
+    /// }
+    /// ```
+    Off,
+}
+
+/// Fields of the `audit` attribute
+struct AuditConfig {
+    actor: Expr,
+    action: Expr,
+    resource: Expr,
+    fields: Vec<(String, Expr)>,
+}
+
+impl Parse for AuditConfig {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let span = input.span();
+        let (mut actor, mut action, mut resource) = (None, None, None);
+        let mut fields = Vec::new();
+        for field in Punctuated::<MetaNameValue, Token![,]>::parse_terminated(input)? {
+            let Some(key) = field.path.get_ident().map(Ident::to_string) else {
+                return Err(syn::Error::new_spanned(field.path, "expected a field name"));
+            };
+            match key.as_str() {
+                "actor" => actor = Some(field.value),
+                "action" => action = Some(field.value),
+                "resource" => resource = Some(field.value),
+                "phase" | "function" | "outcome" | "context" | "timestamp_ms" => {
+                    return Err(syn::Error::new_spanned(
+                        field.path,
+                        format!("`{key}` is set by the audit event itself"),
+                    ))
+                }
+                _ => fields.push((key, field.value)),
+            }
+        }
+        let missing: Vec<&str> = [
+            ("actor", actor.is_none()),
+            ("action", action.is_none()),
+            ("resource", resource.is_none()),
+        ]
+        .into_iter()
+        .filter_map(|(name, missing)| missing.then_some(name))
+        .collect();
+        match (actor, action, resource) {
+            (Some(actor), Some(action), Some(resource)) => Ok(AuditConfig {
+                actor,
+                action,
+                resource,
+                fields,
+            }),
+            _ => Err(syn::Error::new(
+                span,
+                format!(
+                    "`audit(...)` requires `actor`, `action` and `resource`; missing `{}`",
+                    missing.join("`, `")
+                ),
+            )),
+        }
+    }
+}
+
+/// Options of the `metrics` attribute
+struct MetricsConfig {
+    labels: Vec<Expr>,
+    max_label_values: proc_macro2::TokenStream,
+}
+
+impl Parse for MetricsConfig {
+    fn parse(input: syn::parse::ParseStream) -> syn::Result<Self> {
+        let mut config = MetricsConfig {
+            labels: Vec::new(),
+            max_label_values: quote! { ::log_args_runtime::metrics::DEFAULT_MAX_LABEL_VALUES },
+        };
+        while !input.is_empty() {
+            let option: Ident = input.parse()?;
+            if option == "labels" {
+                let content;
+                parenthesized!(content in input);
+                config
+                    .labels
+                    .extend(Punctuated::<Expr, Token![,]>::parse_terminated(&content)?);
+            } else if option == "max_label_values" {
+                input.parse::<Token![=]>()?;
+                let max: syn::LitInt = input.parse()?;
+                config.max_label_values = quote! { #max };
+            } else {
+                return Err(syn::Error::new_spanned(
+                    option,
+                    "expected `labels(...)` or `max_label_values`",
+                ));
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// Parse a bare level name (`trace`, `debug`, `info`, `warn`, `error`) into a `tracing::Level`
+fn parse_level(expr: &Expr) -> syn::Result<proc_macro2::TokenStream> {
+    let level = match expr {
+        Expr::Path(path) => path.path.get_ident().map(|ident| ident.to_string()),
+        _ => None,
+    };
+    let level = match level.as_deref() {
+        Some("trace") => quote! { TRACE },
+        Some("debug") => quote! { DEBUG },
+        Some("info") => quote! { INFO },
+        Some("warn") => quote! { WARN },
+        Some("error") => quote! { ERROR },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                expr,
+                "expected `trace`, `debug`, `info`, `warn` or `error`",
+            ))
+        }
+    };
+    Ok(quote! { ::tracing::Level::#level })
 }
 
 impl Parse for Attribute {
@@ -834,6 +1954,11 @@ impl Parse for Attribute {
             parenthesized!(content in input);
             let custom = Punctuated::<MetaNameValue, Token![,]>::parse_terminated(&content)?;
             Ok(Attribute::Custom(custom))
+        } else if ident == "global" {
+            let content;
+            parenthesized!(content in input);
+            let global = Punctuated::<MetaNameValue, Token![,]>::parse_terminated(&content)?;
+            Ok(Attribute::Global(global))
         } else if ident == "current" {
             let content;
             parenthesized!(content in input);
@@ -855,6 +1980,116 @@ impl Parse for Attribute {
             Ok(Attribute::All)
         } else if ident == "auto_capture" {
             Ok(Attribute::AutoCapture)
+        } else if ident == "buffer" {
+            let mut threshold = quote! { ::tracing::Level::INFO };
+            let mut capacity = quote! { ::log_args_runtime::buffer::DEFAULT_CAPACITY };
+            if input.peek(syn::token::Paren) {
+                let content;
+                parenthesized!(content in input);
+                let options = Punctuated::<MetaNameValue, Token![,]>::parse_terminated(&content)?;
+                for option in options {
+                    let value = &option.value;
+                    if option.path.is_ident("threshold") {
+                        threshold = parse_level(value)?;
+                    } else if option.path.is_ident("capacity") {
+                        capacity = quote! { #value };
+                    } else {
+                        return Err(syn::Error::new_spanned(
+                            option.path,
+                            "expected `threshold` or `capacity`",
+                        ));
+                    }
+                }
+            }
+            Ok(Attribute::Buffer(
+                quote! { ::log_args_runtime::buffer::start(#threshold, #capacity) },
+            ))
+        } else if ident == "dedup" {
+            if input.peek(Token![=]) {
+                input.parse::<Token![=]>()?;
+                let window_ms = parse_duration_ms(&input.parse()?)?;
+                Ok(Attribute::Dedup(
+                    quote! { ::std::time::Duration::from_millis(#window_ms) },
+                ))
+            } else {
+                Ok(Attribute::Dedup(
+                    quote! { ::log_args_runtime::dedup::DEFAULT_WINDOW },
+                ))
+            }
+        } else if ident == "metrics" {
+            if input.peek(syn::token::Paren) {
+                let content;
+                parenthesized!(content in input);
+                Ok(Attribute::Metrics(content.parse()?))
+            } else {
+                Ok(Attribute::Metrics(parse_quote!()))
+            }
+        } else if ident == "audit" {
+            let content;
+            let parens = parenthesized!(content in input);
+            if content.is_empty() {
+                return Err(syn::Error::new(
+                    parens.span.join(),
+                    "`audit(...)` requires `actor`, `action` and `resource`",
+                ));
+            }
+            Ok(Attribute::Audit(content.parse()?))
+        } else if ident == "target" {
+            input.parse::<Token![=]>()?;
+            Ok(Attribute::Target(input.parse()?))
+        } else if ident == "name" {
+            input.parse::<Token![=]>()?;
+            Ok(Attribute::Name(input.parse()?))
+        } else if ident == "min_level" {
+            input.parse::<Token![=]>()?;
+            let level: Expr = input.parse()?;
+            parse_level(&level)?;
+            let rank = LEVEL_MACROS
+                .iter()
+                .position(|name| quote!(#level).to_string() == *name)
+                .unwrap_or_default();
+            Ok(Attribute::MinLevel(rank))
+        } else if ident == "off" {
+            Ok(Attribute::Off)
+        } else if ident == "rewrite_prints" {
+            Ok(Attribute::RewritePrints)
+        } else if ident == "macros" {
+            let content;
+            parenthesized!(content in input);
+            let macros = Punctuated::<syn::Path, Token![,]>::parse_terminated(&content)?;
+            Ok(Attribute::Macros(macros))
+        } else if ident == "sample" {
+            input.parse::<Token![=]>()?;
+            Ok(Attribute::Sample(input.parse()?))
+        } else if ident == "rate_limit" {
+            input.parse::<Token![=]>()?;
+            let (limit, period_ms) = parse_rate_limit(&input.parse()?)?;
+            Ok(Attribute::RateLimit(limit, period_ms))
+        } else if ident == "verbose_if" {
+            input.parse::<Token![=]>()?;
+            Ok(Attribute::VerboseIf(input.parse()?))
+        } else if ident == "wide_event" {
+            Ok(Attribute::WideEvent)
+        } else if ident == "isolate" {
+            Ok(Attribute::Isolate)
+        } else if ident == "on_conflict" {
+            input.parse::<Token![=]>()?;
+            // `override` is a reserved keyword, so accept any identifier here
+            let policy = input.call(Ident::parse_any)?;
+            let variant = match policy.to_string().as_str() {
+                "override" => quote! { Override },
+                "keep_parent" => quote! { KeepParent },
+                "both" => quote! { Both },
+                _ => {
+                    return Err(syn::Error::new_spanned(
+                        policy,
+                        "expected `override`, `keep_parent` or `both`",
+                    ))
+                }
+            };
+            Ok(Attribute::OnConflict(
+                quote! { ::log_args_runtime::ConflictPolicy::#variant },
+            ))
         } else {
             Err(syn::Error::new_spanned(ident, "unknown attribute"))
         }
@@ -864,12 +2099,29 @@ impl Parse for Attribute {
 struct AttrConfig {
     fields: Vec<syn::Expr>,
     custom: Vec<syn::MetaNameValue>,
+    global: Vec<syn::MetaNameValue>,
     current: Vec<syn::Expr>,
     clone_upfront: bool,
     span: bool,
     span_fields: Vec<syn::Expr>,
     all_params: bool,
     auto_capture: bool, // New field for automatic closure context capture
+    on_conflict: Option<proc_macro2::TokenStream>,
+    isolate: bool,
+    wide_event: bool,
+    buffer: Option<proc_macro2::TokenStream>,
+    verbose_if: Option<syn::Expr>,
+    sample: Option<syn::Expr>,
+    rate_limit: Option<(u64, u64)>,
+    dedup: Option<proc_macro2::TokenStream>,
+    metrics: Option<MetricsConfig>,
+    audit: Option<AuditConfig>,
+    target: Option<syn::Expr>,
+    name: Option<syn::Expr>,
+    min_level: Option<usize>,
+    macros: Vec<syn::Path>,
+    rewrite_prints: bool,
+    off: bool,
 }
 
 impl Default for AttrConfig {
@@ -877,23 +2129,52 @@ impl Default for AttrConfig {
         Self {
             fields: Vec::new(),
             custom: Vec::new(),
+            global: Vec::new(),
             current: Vec::new(),
             clone_upfront: true, // Default to true for safety
             span: false,         // Disable span by default per requirement
             span_fields: Vec::new(),
             all_params: false,
             auto_capture: false, // Default to false for auto_capture
+            on_conflict: None,
+            isolate: false,
+            wide_event: false,
+            buffer: None,
+            verbose_if: None,
+            sample: None,
+            rate_limit: None,
+            dedup: None,
+            metrics: None,
+            audit: None,
+            target: None,
+            name: None,
+            min_level: None,
+            macros: Vec::new(),
+            rewrite_prints: false,
+            off: false,
         }
     }
 }
 
 impl AttrConfig {
+    /// Whether the function pushes context frames or call-tree state, or hides them
+    fn holds_frames(&self) -> bool {
+        self.span
+            || self.isolate
+            || self.buffer.is_some()
+            || self.verbose_if.is_some()
+            || self.sample.is_some()
+            || self.rate_limit.is_some()
+            || self.dedup.is_some()
+    }
+
     fn from_attributes(attrs: Punctuated<Attribute, Token![,]>) -> Self {
         let mut config = AttrConfig::default();
         for attr in attrs {
             match attr {
                 Attribute::Fields(fields) => config.fields.extend(fields),
                 Attribute::Custom(custom) => config.custom.extend(custom),
+                Attribute::Global(global) => config.global.extend(global),
                 Attribute::Current(current) => config.current.extend(current),
                 Attribute::CloneUpfront => config.clone_upfront = true,
                 Attribute::Span(span_fields) => {
@@ -907,6 +2188,30 @@ impl AttrConfig {
                 Attribute::AutoCapture => {
                     config.auto_capture = true;
                 }
+                Attribute::OnConflict(policy) => {
+                    config.on_conflict = Some(policy);
+                }
+                Attribute::Isolate => config.isolate = true,
+                Attribute::Buffer(start) => config.buffer = Some(start),
+                Attribute::VerboseIf(condition) => config.verbose_if = Some(condition),
+                Attribute::Sample(rate) => config.sample = Some(rate),
+                Attribute::Dedup(window) => config.dedup = Some(window),
+                Attribute::Metrics(metrics) => config.metrics = Some(metrics),
+                Attribute::Audit(audit) => config.audit = Some(*audit),
+                Attribute::Target(target) => config.target = Some(target),
+                Attribute::Name(name) => config.name = Some(name),
+                Attribute::MinLevel(rank) => config.min_level = Some(rank),
+                Attribute::Macros(macros) => config.macros.extend(macros),
+                Attribute::RewritePrints => config.rewrite_prints = true,
+                Attribute::Off => config.off = true,
+                Attribute::RateLimit(limit, period_ms) => {
+                    config.rate_limit = Some((limit, period_ms));
+                }
+                Attribute::WideEvent => {
+                    config.wide_event = true;
+                    config.span = true; // Recorded fields need a frame
+                    config.clone_upfront = true;
+                }
             }
         }
         config
@@ -925,6 +2230,7 @@ fn get_context_fields_quote(item: &FnItem, config: &AttrConfig) -> Vec<proc_macr
     if config.span
         && config.fields.is_empty()
         && config.custom.is_empty()
+        && config.global.is_empty()
         && config.current.is_empty()
         && !config.all_params
     {
@@ -1033,6 +2339,15 @@ fn get_context_fields_quote(item: &FnItem, config: &AttrConfig) -> Vec<proc_macr
         });
     }
 
+    // Add global fields (logged here and published process-wide)
+    for (index, nv) in config.global.iter().enumerate() {
+        let key = &nv.path;
+        let binding = global_binding(index);
+        field_assignments.push(quote! {
+            #key = #binding
+        });
+    }
+
     // Add current fields (only logged in current function, not propagated)
     for current_field in &config.current {
         let field_name = quote! { #current_field }.to_string();
@@ -1092,6 +2407,7 @@ fn get_context_fields_quote(item: &FnItem, config: &AttrConfig) -> Vec<proc_macr
 
 /// Add function name field to log output when any function-names feature is enabled.
 /// The function name will be formatted according to the enabled feature.
+#[allow(unused_variables, clippy::ptr_arg)]
 fn add_function_name_field(field_assignments: &mut Vec<proc_macro2::TokenStream>, item: &FnItem) {
     // Check if any function-names feature is enabled
     #[cfg(any(
@@ -1148,15 +2464,11 @@ fn get_context_map_for_span(_item: &FnItem, config: &AttrConfig) -> proc_macro2:
         let key_str = quote!(#key).to_string().replace(' ', "");
 
         // For span context, use the original expression directly
-        // This will be evaluated before any moves happen
+        // This will be evaluated before any moves happen.
+        // Custom fields stay scoped to this call tree; only `global(...)` is process-wide.
         fields_to_log.push(quote! {
             new_context.insert(#key_str.to_string(), format!("{}", #value));
         });
-
-        // Also store globally for cross-boundary persistence
-        fields_to_log.push(quote! {
-            ::log_args_runtime::set_global_context(&#key_str, &format!("{}", #value));
-        });
     }
 
     // 4. Add current fields (these are also stored in context for consistency)
@@ -1172,7 +2484,7 @@ fn get_context_map_for_span(_item: &FnItem, config: &AttrConfig) -> proc_macro2:
 
     quote! {
         {
-            let mut new_context = ::std::collections::HashMap::new();
+            let mut new_context = ::log_args_runtime::ContextFrame::new();
             #(#fields_to_log)*
             new_context
         }
@@ -1240,37 +2552,224 @@ impl FnItem {
     }
 }
 
+/// Level macros from most to least severe
+const LEVEL_MACROS: [&str; 5] = ["error", "warn", "info", "debug", "trace"];
+
+/// Span macros with a fixed level
+const SPAN_MACROS: [&str; 5] = [
+    "error_span",
+    "warn_span",
+    "info_span",
+    "debug_span",
+    "trace_span",
+];
+
 fn get_log_redefines_with_fields(
     context_fields: &[proc_macro2::TokenStream],
+    config: &AttrConfig,
     _is_async: bool,
 ) -> proc_macro2::TokenStream {
+    // Function-level `name:` / `target:` go first, so a statement's own prefix wins
+    let name = config.name.iter().map(|name| quote! { name: #name, });
+    let target = config
+        .target
+        .iter()
+        .map(|target| quote! { target: #target, });
+    let prefix = quote! { {#(#name)*} {#(#target)*} {} };
+    let enabled = config.min_level.map_or(LEVEL_MACROS.len(), |min| min + 1);
+    let fields = quote! { [#(#context_fields,)*] };
+
     // Always redefine macros to include both local fields and inherited context
     // The context inheritance will be handled by including context fields from the runtime
-    quote! {
-        macro_rules! info {
-            ($($t:tt)*) => {
-                ::log_args_runtime::log_with_context!(::tracing::info, ::log_args_runtime::get_context(), #(#context_fields,)* $($t)*)
-            };
+    let redefines = LEVEL_MACROS.iter().enumerate().map(|(rank, level)| {
+        let constant = Ident::new(&level.to_ascii_uppercase(), proc_macro2::Span::call_site());
+        let level = Ident::new(level, proc_macro2::Span::call_site());
+        // Disabled levels return before sampling, dedup or the context are touched
+        let log = quote! {
+            if ::tracing::level_enabled!(::tracing::Level::#constant) {
+                ::log_args_runtime::__log_with_context!(@prefix ::tracing::#level, ::log_args_runtime::get_context(), #prefix #fields $($t)*)
+            }
+        };
+        // Silenced levels still type-check their arguments, so no variable becomes unused
+        let body = if rank < enabled {
+            log
+        } else {
+            quote! { if false { #log } }
+        };
+        quote! {
+            macro_rules! #level {
+                ($($t:tt)*) => {
+                    #body
+                };
+            }
         }
-        macro_rules! warn {
-            ($($t:tt)*) => {
-                ::log_args_runtime::log_with_context!(::tracing::warn, ::log_args_runtime::get_context(), #(#context_fields,)* $($t)*)
-            };
+    });
+    // `event!` levels are expressions, so `min_level` is checked when the event is logged
+    let max_level = Ident::new(
+        &LEVEL_MACROS[enabled - 1].to_ascii_uppercase(),
+        proc_macro2::Span::call_site(),
+    );
+    // Wrappers choose their own level and target, so only the fields and context are added
+    let wrapper = if config.macros.is_empty() {
+        quote! {}
+    } else {
+        quote! {
+            macro_rules! __log_args_wrapper {
+                ($wrapper:path, $($t:tt)*) => {
+                    ::log_args_runtime::__log_with_context!(@prefix $wrapper, ::log_args_runtime::get_context(), {} {} {} #fields $($t)*)
+                };
+            }
         }
-        macro_rules! error {
-            ($($t:tt)*) => {
-                ::log_args_runtime::log_with_context!(::tracing::error, ::log_args_runtime::get_context(), #(#context_fields,)* $($t)*)
-            };
+    };
+    // The user's crate decides between debug events and plain printing
+    let prints = if config.rewrite_prints {
+        quote! {
+            #[cfg(debug_assertions)]
+            macro_rules! __log_args_dbg {
+                () => {
+                    debug!("[{}:{}:{}]", file!(), line!(), column!())
+                };
+                ($val:expr $(,)?) => {
+                    match $val {
+                        tmp => {
+                            debug!("[{}:{}:{}] {} = {:?}", file!(), line!(), column!(), stringify!($val), &tmp);
+                            tmp
+                        }
+                    }
+                };
+                ($($val:expr),+ $(,)?) => {
+                    ($(__log_args_dbg!($val)),+,)
+                };
+            }
+            #[cfg(not(debug_assertions))]
+            macro_rules! __log_args_dbg {
+                ($($t:tt)*) => {
+                    ::std::dbg!($($t)*)
+                };
+            }
+            #[cfg(debug_assertions)]
+            macro_rules! __log_args_print {
+                ($print:ident,) => {
+                    debug!("")
+                };
+                ($print:ident, $($t:tt)*) => {
+                    debug!($($t)*)
+                };
+            }
+            #[cfg(not(debug_assertions))]
+            macro_rules! __log_args_print {
+                ($print:ident, $($t:tt)*) => {
+                    ::std::$print!($($t)*)
+                };
+            }
         }
-        macro_rules! debug {
+    } else {
+        quote! {}
+    };
+    let span_redefines = SPAN_MACROS.iter().map(|span_macro| {
+        let span_macro = Ident::new(span_macro, proc_macro2::Span::call_site());
+        quote! {
+            macro_rules! #span_macro {
+                ($($t:tt)*) => {
+                    ::log_args_runtime::__span_with_context!(@name ::tracing::#span_macro, ::log_args_runtime::get_context(), {} {} #fields $($t)*)
+                };
+            }
+        }
+    });
+    quote! {
+        #(#redefines)*
+        macro_rules! event {
             ($($t:tt)*) => {
-                ::log_args_runtime::log_with_context!(::tracing::debug, ::log_args_runtime::get_context(), #(#context_fields,)* $($t)*)
+                ::log_args_runtime::__log_with_context!(@event ::log_args_runtime::get_context(), ::tracing::Level::#max_level, #prefix #fields $($t)*)
             };
         }
-        macro_rules! trace {
+        macro_rules! span {
             ($($t:tt)*) => {
-                ::log_args_runtime::log_with_context!(::tracing::trace, ::log_args_runtime::get_context(), #(#context_fields,)* $($t)*)
+                ::log_args_runtime::__span_with_context!(@level ::tracing::span, ::log_args_runtime::get_context(), {} {} #fields $($t)*)
             };
         }
+        #(#span_redefines)*
+        #wrapper
+        #prints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_fn() -> proc_macro2::TokenStream {
+        quote! {
+            async fn handle(order_id: u64) -> Result<(), String> {
+                info!("Handling");
+                tokio::spawn(async move { process(order_id).await });
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_off_returns_input_unchanged() {
+        let input = sample_fn();
+        let output = params_impl(quote! { off, span, fields(order_id) }, input.clone());
+        assert_eq!(output.to_string(), input.to_string());
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    #[test]
+    fn test_off_still_reports_unknown_attributes() {
+        let output = params_impl(quote! { off, not_an_attribute }, sample_fn());
+        assert!(output.to_string().contains("unknown attribute"), "{output}");
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    #[test]
+    fn test_enabled_rewrites_input() {
+        let input = sample_fn();
+        let output = params_impl(quote! { span, fields(order_id) }, input.clone());
+        assert_ne!(output.to_string(), input.to_string());
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    #[test]
+    fn test_on_conflict_requires_span() {
+        let output = params_impl(quote! { fields(order_id), on_conflict = both }, sample_fn());
+        assert!(output.to_string().contains("add `span`"), "{output}");
+
+        let output = params_impl(quote! { span, on_conflict = both }, sample_fn());
+        assert!(!output.to_string().contains("compile_error"), "{output}");
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    #[test]
+    fn test_rewrite_prints_requires_debug() {
+        let output = params_impl(quote! { rewrite_prints, min_level = info }, sample_fn());
+        assert!(output.to_string().contains("min_level = debug"), "{output}");
+
+        let output = params_impl(quote! { rewrite_prints, min_level = debug }, sample_fn());
+        assert!(!output.to_string().contains("compile_error"), "{output}");
+    }
+
+    #[cfg(not(feature = "disabled"))]
+    #[test]
+    fn test_async_body_wrapped_only_when_holding_frames() {
+        let output = params_impl(quote! { fields(order_id) }, sample_fn());
+        assert!(!output.to_string().contains("async_body"), "{output}");
+
+        for attrs in [quote! { span }, quote! { dedup }, quote! { sample = 0.5 }] {
+            let output = params_impl(attrs, sample_fn());
+            assert!(output.to_string().contains("async_body"), "{output}");
+        }
+    }
+
+    #[cfg(feature = "disabled")]
+    #[test]
+    fn test_disabled_feature_returns_input_unchanged() {
+        let input = sample_fn();
+        let output = params_impl(
+            quote! { span, fields(order_id), not_an_attribute },
+            input.clone(),
+        );
+        assert_eq!(output.to_string(), input.to_string());
     }
 }
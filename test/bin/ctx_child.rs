@@ -0,0 +1,18 @@
+//! Helper binary for the child-process propagation tests.
+//!
+//! Seeds its root context from the environment and prints the merged context as
+//! sorted `key=value` lines.
+
+fn main() {
+    let prefix = std::env::args().nth(1);
+    match prefix {
+        Some(prefix) => log_args_runtime::process::init_from_env_with_prefix(&prefix),
+        None => log_args_runtime::init_from_env(),
+    };
+
+    let mut fields: Vec<_> = log_args_runtime::get_context().into_iter().collect();
+    fields.sort();
+    for (key, value) in fields {
+        println!("{key}={value}");
+    }
+}
@@ -0,0 +1,115 @@
+//! Tests for the audit event stream
+//!
+//! Tests that `#[params(audit(...))]` records start and outcome events with actor,
+//! action, resource and inherited context, routed to the sink set with `audit::set_sink`
+
+use log_args::params;
+use log_args_runtime::audit::{self, WriterSink};
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::sync::Once;
+
+struct User {
+    name: String,
+}
+
+#[params(span, fields(request_id))]
+fn handle_request(request_id: &str, user: &User, doc_id: u64) -> Result<(), String> {
+    delete_document(user, doc_id)
+}
+
+#[params(audit(actor = user.name, action = "delete", resource = doc_id, reason = "cleanup"))]
+fn delete_document(user: &User, doc_id: u64) -> Result<(), String> {
+    if doc_id == 0 {
+        return Err("no such document".to_string());
+    }
+    info!("Deleting document");
+    Ok(())
+}
+
+#[params(audit(actor = actor, action = "rotate", resource = "signing-key"))]
+fn rotate_key(actor: &str) {
+    panic!("HSM unavailable");
+}
+
+/// Audit file shared by all tests in this binary; the sink is process-wide
+fn audit_file() -> PathBuf {
+    static INIT: Once = Once::new();
+    let path = std::env::temp_dir().join(format!("log_args_audit_{}.log", std::process::id()));
+    INIT.call_once(|| {
+        audit::set_sink(WriterSink::new(File::create(&path).unwrap()));
+    });
+    path
+}
+
+/// Audit lines written for `actor`, in order
+fn audit_lines(actor: &str) -> Vec<String> {
+    let needle = format!(r#""actor":"{actor}""#);
+    fs::read_to_string(audit_file())
+        .unwrap()
+        .lines()
+        .filter(|line| line.contains(&needle))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_start_and_outcome_with_context() {
+        audit_file();
+        let user = User {
+            name: "alice".to_string(),
+        };
+        handle_request("req-1", &user, 42).unwrap();
+
+        let lines = audit_lines("alice");
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains(r#""phase":"start""#), "{}", lines[0]);
+        assert!(!lines[0].contains(r#""outcome""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""phase":"outcome""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""outcome":"ok""#), "{}", lines[1]);
+        for line in &lines {
+            assert!(line.contains(r#""function":"delete_document""#), "{line}");
+            assert!(line.contains(r#""action":"delete""#), "{line}");
+            assert!(line.contains(r#""resource":"42""#), "{line}");
+            assert!(line.contains(r#""reason":"cleanup""#), "{line}");
+            assert!(
+                line.contains(r#""context":{"request_id":"\"req-1\""}"#),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_outcome() {
+        audit_file();
+        let user = User {
+            name: "bob".to_string(),
+        };
+        delete_document(&user, 0).unwrap_err();
+
+        let lines = audit_lines("bob");
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[1].contains(r#""outcome":"error""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""context":{}"#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_panic_outcome() {
+        audit_file();
+        let result = std::panic::catch_unwind(|| rotate_key("carol"));
+        assert!(result.is_err());
+
+        let lines = audit_lines("carol");
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(
+            lines[0].contains(r#""resource":"signing-key""#),
+            "{}",
+            lines[0]
+        );
+        assert!(lines[1].contains(r#""outcome":"panic""#), "{}", lines[1]);
+    }
+}
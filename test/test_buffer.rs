@@ -0,0 +1,173 @@
+//! Tests for tail-based buffered logging
+//!
+//! Tests that `#[params(buffer)]` drops the debug trail of successful calls and writes
+//! it, with original timestamps, when the call tree fails, and that the buffer follows
+//! async tasks rather than the thread
+
+mod common;
+
+use common::CapturedLogs;
+use log_args::params;
+use log_args_runtime::buffer::BufferedMakeWriter;
+use std::time::Duration;
+
+#[derive(Debug)]
+struct SettleError;
+
+#[params(buffer, fields(order_id))]
+fn settle(order_id: u64) -> Result<(), SettleError> {
+    debug!("Loading ledger");
+    post_entries(order_id);
+    info!("Entries posted");
+    if order_id == 0 {
+        std::thread::sleep(Duration::from_millis(20));
+        info!("Rejecting order");
+        return Err(SettleError);
+    }
+    Ok(())
+}
+
+#[params(span, fields(order_id))]
+fn post_entries(order_id: u64) {
+    trace!("Posting entries");
+}
+
+#[params(buffer(threshold = warn, capacity = 2))]
+fn reconcile(fail: bool) {
+    debug!("Step 1");
+    info!("Step 2");
+    info!("Step 3");
+    if fail {
+        error!("Reconcile failed");
+    }
+    debug!("After failure");
+}
+
+#[params(buffer, fields(job))]
+async fn run_job(job: u64) -> Result<(), SettleError> {
+    debug!("Job started");
+    tokio::task::yield_now().await;
+    tokio::spawn(async move {
+        debug!("Spawned step");
+    })
+    .await
+    .unwrap();
+    if job == 0 {
+        return Err(SettleError);
+    }
+    Ok(())
+}
+
+#[params]
+async fn unbuffered_task() {
+    tokio::task::yield_now().await;
+    debug!("Unbuffered task");
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+fn capture_buffered<F: FnOnce()>(f: F) -> CapturedLogs {
+    let logs = CapturedLogs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_max_level(tracing::Level::TRACE)
+        .with_writer(BufferedMakeWriter::new(logs.clone()))
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_success_discards_buffer() {
+        let logs = capture_buffered(|| {
+            settle(1).unwrap();
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("Entries posted"));
+        assert!(!log_args_runtime::buffer::is_active());
+    }
+
+    #[test]
+    fn test_err_flushes_nested_events_with_original_timestamps() {
+        let logs = capture_buffered(|| {
+            assert!(settle(0).is_err());
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        let debug = lines.iter().position(|l| l.contains("Loading ledger"));
+        let trace = lines.iter().position(|l| l.contains("Posting entries"));
+        let reject = lines.iter().position(|l| l.contains("Rejecting order"));
+        assert!(debug.is_some() && trace.is_some() && reject.is_some());
+
+        // Flushed lines were formatted when logged, 20ms before the rejection
+        let timestamp = |line: &str| line.split_whitespace().next().unwrap().to_string();
+        let debug_line = &lines[debug.unwrap()];
+        let reject_line = &lines[reject.unwrap()];
+        assert!(
+            timestamp(debug_line) < timestamp(reject_line),
+            "{debug_line}\n{reject_line}"
+        );
+    }
+
+    #[test]
+    fn test_error_event_flushes_ring_buffer() {
+        let logs = capture_buffered(|| reconcile(true));
+
+        let lines = logs.lines();
+        // Capacity 2 keeps the two most recent buffered events
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        assert!(lines[0].contains("Step 2"));
+        assert!(lines[1].contains("Step 3"));
+        assert!(lines[2].contains("Reconcile failed"));
+    }
+
+    #[test]
+    fn test_threshold_and_unbuffered_events() {
+        let logs = capture_buffered(|| {
+            reconcile(false);
+            log_args_runtime::debug!("Outside any buffer");
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("Outside any buffer"));
+    }
+
+    #[test]
+    fn test_other_tasks_on_the_thread_are_not_buffered() {
+        let logs = capture_buffered(|| {
+            block_on(async {
+                let (result, ()) = tokio::join!(run_job(1), unbuffered_task());
+                result.unwrap();
+            })
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("Unbuffered task"));
+    }
+
+    #[test]
+    fn test_spawned_tasks_share_the_buffer() {
+        let logs = capture_buffered(|| {
+            assert!(block_on(run_job(0)).is_err());
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("Job started"));
+        assert!(lines[1].contains("Spawned step"));
+    }
+}
@@ -0,0 +1,121 @@
+//! Tests for the context merge order and per-function conflict policies
+//!
+//! Tests `on_conflict = override | keep_parent | both` and that parent keys are
+//! never dropped from the inherited fields
+
+use log_args::params;
+use log_args_runtime::{get_context_value, get_inherited_fields_map, ContextFrame};
+
+#[params(span, custom(user_id = "admin-1", request_id = "req-1"))]
+fn admin_request(child: fn() -> ContextFrame) -> ContextFrame {
+    child()
+}
+
+#[params(span, custom(user_id = "user-2"))]
+fn override_child() -> ContextFrame {
+    get_inherited_fields_map()
+}
+
+#[params(span, custom(user_id = "user-2"), on_conflict = override)]
+fn explicit_override_child() -> ContextFrame {
+    get_inherited_fields_map()
+}
+
+#[params(span, custom(user_id = "user-2", note = "kept"), on_conflict = keep_parent)]
+fn keep_parent_child() -> ContextFrame {
+    get_inherited_fields_map()
+}
+
+#[params(span, custom(user_id = "user-2", request_id = "req-1"), on_conflict = both)]
+fn impersonating_child() -> ContextFrame {
+    info!("Admin acting as user");
+    get_inherited_fields_map()
+}
+
+#[params(span, custom(user_id = "admin-1"))]
+async fn async_admin_request() -> ContextFrame {
+    async_impersonating_child().await
+}
+
+#[params(span, custom(user_id = "user-2"), on_conflict = both)]
+async fn async_impersonating_child() -> ContextFrame {
+    get_inherited_fields_map()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(frame: &ContextFrame) -> Vec<(&str, &str)> {
+        frame
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_override_is_default() {
+        let expected = [("user_id", "user-2"), ("request_id", "req-1")];
+        assert_eq!(pairs(&admin_request(override_child)), expected);
+        assert_eq!(pairs(&admin_request(explicit_override_child)), expected);
+    }
+
+    #[test]
+    fn test_keep_parent() {
+        assert_eq!(
+            pairs(&admin_request(keep_parent_child)),
+            [
+                ("user_id", "admin-1"),
+                ("request_id", "req-1"),
+                ("note", "kept")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_both_keeps_parent_value() {
+        // Equal values are not duplicated; differing ones keep the parent's as parent.<key>
+        assert_eq!(
+            pairs(&admin_request(impersonating_child)),
+            [
+                ("user_id", "user-2"),
+                ("request_id", "req-1"),
+                ("parent.user_id", "admin-1")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_conflict_without_parent() {
+        assert_eq!(pairs(&impersonating_child())[0], ("user_id", "user-2"));
+        assert_eq!(impersonating_child().get("parent.user_id"), None);
+    }
+
+    #[tokio::test]
+    async fn test_both_in_async_functions() {
+        let fields = async_admin_request().await;
+        assert_eq!(fields["user_id"], "user-2");
+        assert_eq!(fields["parent.user_id"], "admin-1");
+    }
+
+    #[test]
+    fn test_merge_order() {
+        log_args_runtime::set_global_context("merge_layer", "global");
+        let async_guard = log_args_runtime::push_async_context(ContextFrame::from_iter([(
+            "merge_layer".to_string(),
+            "async".to_string(),
+        )]));
+        assert_eq!(get_context_value("merge_layer").as_deref(), Some("async"));
+        {
+            let _sync_guard = log_args_runtime::push_context(ContextFrame::from_iter([(
+                "merge_layer".to_string(),
+                "sync".to_string(),
+            )]));
+            assert_eq!(get_context_value("merge_layer").as_deref(), Some("sync"));
+            assert_eq!(get_inherited_fields_map()["merge_layer"], "sync");
+        }
+        drop(async_guard);
+        assert_eq!(get_inherited_fields_map()["merge_layer"], "global");
+        log_args_runtime::remove_global_context("merge_layer");
+    }
+}
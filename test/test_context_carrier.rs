@@ -0,0 +1,108 @@
+//! Tests for the ContextCarrier trait
+//!
+//! Tests that HTTP headers, message-queue header lists and plain maps all share
+//! the same propagation rules
+
+use log_args::params;
+use log_args_runtime::carrier::extract_pairs;
+use log_args_runtime::propagation::PropagationConfig;
+use log_args_runtime::{ContextCarrier, ContextFrame, ContextSnapshot};
+use std::collections::HashMap;
+
+// Producer publishing a message from inside a function with span context
+#[params(span, fields(order_id))]
+fn publish_order(order_id: u64) -> Vec<(String, Vec<u8>)> {
+    let mut headers: Vec<(String, Vec<u8>)> = Vec::new();
+    headers.inject(&ContextSnapshot::capture(), &config());
+    headers
+}
+
+fn config() -> PropagationConfig {
+    PropagationConfig::new(["order_id", "tenant_id"])
+}
+
+fn snapshot() -> ContextSnapshot {
+    let mut fields = ContextFrame::new();
+    fields.insert("tenant_id".to_string(), "acme".to_string());
+    fields.insert("password".to_string(), "hunter2".to_string());
+    fields.insert(
+        "trace_id".to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+    );
+    fields.insert("span_id".to_string(), "00f067aa0ba902b7".to_string());
+    ContextSnapshot::from(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash_map_carrier() {
+        let mut carrier: HashMap<String, String> = HashMap::new();
+        carrier.inject(&snapshot(), &config());
+
+        let frame = carrier.extract(&config());
+        assert_eq!(frame.len(), 1);
+        assert_eq!(frame["tenant_id"], "acme");
+    }
+
+    #[test]
+    fn test_kafka_style_header_list() {
+        let headers = publish_order(42);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].0, "baggage");
+
+        let frame = headers.extract(&config());
+        assert_eq!(frame["order_id"], "42");
+    }
+
+    #[test]
+    fn test_string_pair_list_replaces_existing_key() {
+        let mut headers: Vec<(String, String)> =
+            vec![("Baggage".to_string(), "tenant_id=old".to_string())];
+        headers.inject(&snapshot(), &config());
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.extract(&config())["tenant_id"], "acme");
+    }
+
+    #[test]
+    fn test_extract_from_read_only_iterator() {
+        let raw: [(&str, &[u8]); 2] = [
+            ("content-type", b"application/json"),
+            ("baggage", b"tenant_id=acme,password=hunter2"),
+        ];
+
+        let frame = extract_pairs(raw.iter().copied(), &config());
+        assert_eq!(frame.len(), 1);
+        assert_eq!(frame["tenant_id"], "acme");
+    }
+
+    #[test]
+    fn test_http_header_map_carrier() {
+        let mut headers = http::HeaderMap::new();
+        headers.inject(&snapshot(), &config().with_traceparent(true));
+
+        assert_eq!(headers["baggage"], "tenant_id=acme");
+        assert!(headers.contains_key("traceparent"));
+
+        let frame = headers.extract(&config().with_traceparent(true));
+        assert_eq!(frame["tenant_id"], "acme");
+        assert!(frame.contains_key("trace_id"));
+    }
+
+    #[test]
+    fn test_same_rules_for_every_carrier() {
+        let mut map: HashMap<String, String> = HashMap::new();
+        let mut list: Vec<(String, Vec<u8>)> = Vec::new();
+        let mut http_headers = http::HeaderMap::new();
+        map.inject(&snapshot(), &config());
+        list.inject(&snapshot(), &config());
+        http_headers.inject(&snapshot(), &config());
+
+        let expected = map.extract(&config());
+        assert_eq!(list.extract(&config()), expected);
+        assert_eq!(http_headers.extract(&config()), expected);
+    }
+}
@@ -0,0 +1,99 @@
+//! Tests for filtering events by context field values
+//!
+//! Tests `ContextFilter` rules parsed from strings and the environment, evaluated
+//! against the current log-args context
+
+mod common;
+
+use common::CapturedLogs;
+use log_args::params;
+use log_args_runtime::filter::{ContextFilter, VerboseFilter};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+
+const RULES: &str = "info; tenant_id=acme => debug; route=/health => off";
+
+#[params(span, fields(tenant_id))]
+fn handle(tenant_id: &str) {
+    info!("Handling request");
+    debug!("Request details");
+}
+
+#[params(span, fields(route))]
+fn serve(route: &str) {
+    warn!("Serving route");
+}
+
+fn capture_with<F: FnOnce()>(filter: ContextFilter, f: F) -> Vec<String> {
+    let logs = CapturedLogs::default();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(logs.clone())
+        .with_filter(filter);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+    logs.lines()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_enables_debug_for_one_tenant() {
+        let lines = capture_with(RULES.parse().unwrap(), || {
+            handle("acme");
+            handle("globex");
+        });
+
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        assert!(lines[1].contains("Request details"));
+        assert!(lines[2].contains("globex"));
+    }
+
+    #[test]
+    fn test_rule_drops_health_checks() {
+        let filter =
+            ContextFilter::new(LevelFilter::INFO).rule("route", "/health", LevelFilter::OFF);
+        let lines = capture_with(filter, || {
+            serve("/health");
+            serve("/orders");
+        });
+
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("/orders"));
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let filter: ContextFilter = "warn\nuser_id = 7 => trace".parse().unwrap();
+        assert_eq!(filter.rules().len(), 1);
+        assert_eq!(filter.rules()[0].key, "user_id");
+        assert_eq!(filter.rules()[0].value, "7");
+        assert_eq!(filter.rules()[0].level, LevelFilter::TRACE);
+        assert_eq!(filter.current_level(), LevelFilter::WARN);
+
+        assert!("tenant_id => debug".parse::<ContextFilter>().is_err());
+        assert!("tenant_id=acme => loud".parse::<ContextFilter>().is_err());
+    }
+
+    #[test]
+    fn test_rules_from_env_and_verbose_composition() {
+        std::env::set_var("CONTEXT_FILTER_TEST_RULES", "error");
+        let filter = ContextFilter::from_env_var("CONTEXT_FILTER_TEST_RULES").unwrap();
+        assert_eq!(filter.current_level(), LevelFilter::ERROR);
+        assert_eq!(
+            ContextFilter::from_env_var("CONTEXT_FILTER_TEST_UNSET").unwrap(),
+            ContextFilter::default()
+        );
+
+        let logs = CapturedLogs::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(logs.clone())
+            .with_filter(VerboseFilter::new(filter));
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            handle("globex");
+            let _verbose = log_args_runtime::filter::mark_verbose(true);
+            handle("initech");
+        });
+        assert_eq!(logs.lines().len(), 2);
+    }
+}
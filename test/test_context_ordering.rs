@@ -0,0 +1,71 @@
+//! Tests for deterministic, insertion-ordered context fields
+//!
+//! Tests that parent keys come first, in stack order, on every run
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+
+#[params(span, custom(request_id = "req-1", tenant_id = "acme", zone = "a"))]
+fn ordered_parent() -> (String, Vec<String>) {
+    ordered_child()
+}
+
+#[params(span, custom(user_id = "u-9", batch = 3, zone = "b"))]
+fn ordered_child() -> (String, Vec<String>) {
+    info!("Child with inherited context");
+    (
+        log_args_runtime::get_inherited_context_string(),
+        log_args_runtime::get_inherited_fields_map()
+            .keys()
+            .cloned()
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inherited_context_string_order() {
+        for _ in 0..20 {
+            let (context, _) = ordered_parent();
+            assert_eq!(
+                context,
+                "request_id=req-1,tenant_id=acme,zone=b,user_id=u-9,batch=3"
+            );
+        }
+    }
+
+    #[test]
+    fn test_inherited_fields_map_order() {
+        for _ in 0..20 {
+            let (_, keys) = ordered_parent();
+            assert_eq!(
+                keys,
+                ["request_id", "tenant_id", "zone", "user_id", "batch"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_emitted_context_field_order() {
+        let mut outputs = Vec::new();
+        for _ in 0..20 {
+            let logs = capture_json(|| {
+                ordered_parent();
+            });
+            let line = logs.lines().remove(0);
+            let start = line.find(r#""context":"#).unwrap();
+            let end = line[start..].find('}').unwrap();
+            outputs.push(line[start..start + end].to_string());
+        }
+
+        assert!(outputs[0].contains(
+            r#"\"request_id\": \"req-1\", \"tenant_id\": \"acme\", \"zone\": \"b\", \"user_id\": \"u-9\", \"batch\": \"3\""#
+        ));
+        assert!(outputs.iter().all(|output| output == &outputs[0]));
+    }
+}
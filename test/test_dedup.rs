@@ -0,0 +1,202 @@
+//! Tests for deduplicating repeated log lines
+//!
+//! Tests that `#[params(dedup)]` collapses identical (callsite, message, context) events
+//! into one line plus a "repeated N times" follow-up, scoped to the function's frame
+//! and task, and logged under the target of the event it counts
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use std::time::Duration;
+
+#[params(dedup, fields(host))]
+fn connect_with_retry(host: &str, attempts: u32) {
+    for _ in 0..attempts {
+        warn!("Connection refused, retrying");
+    }
+    info!("Giving up");
+}
+
+#[params(dedup, fields(host))]
+fn numbered_retries(host: &str) {
+    for attempt in 0..3 {
+        warn!(attempt, "Attempt {} failed", attempt);
+    }
+}
+
+#[params(dedup = "50ms", span, fields(job))]
+fn windowed(job: &str) {
+    for round in 0..3 {
+        if round == 2 {
+            std::thread::sleep(Duration::from_millis(60));
+        }
+        info!(phase = "poll", "Polling");
+    }
+    poll_child(1);
+    poll_child(1);
+    poll_child(2);
+}
+
+#[params(span, fields(shard))]
+fn poll_child(shard: u32) {
+    debug!(shard.kind = "primary", "Child polling");
+}
+
+#[allow(clippy::too_many_arguments)]
+#[params(dedup, all)]
+fn many_fields(a: u8, b: u8, c: u8, d: u8, e: u8, f: u8, g: u8, h: u8, i: u8, j: u8) {
+    for _ in 0..2 {
+        info!(extra = ?(a, b), "Many fields");
+    }
+}
+
+#[params(dedup)]
+fn targeted_retries() {
+    for _ in 0..3 {
+        warn!(target: "retry", "Backing off");
+    }
+}
+
+#[params(dedup, fields(queue))]
+async fn drain(queue: &str) {
+    for _ in 0..3 {
+        warn!("Queue empty");
+        tokio::task::yield_now().await;
+    }
+}
+
+#[params(fields(worker))]
+async fn heartbeat(worker: u32) {
+    for _ in 0..3 {
+        info!("Heartbeat");
+        tokio::task::yield_now().await;
+    }
+}
+
+fn messages(lines: &[String]) -> Vec<String> {
+    lines
+        .iter()
+        .map(|line| {
+            let start = line.find(r#""message":""#).unwrap() + 11;
+            let end = line[start..].find('"').unwrap();
+            line[start..start + end].to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_loop_collapsed() {
+        let logs = capture_json(|| connect_with_retry("db", 50));
+
+        let lines = logs.lines();
+        assert_eq!(
+            messages(&lines),
+            [
+                "Connection refused, retrying",
+                "Giving up",
+                "Connection refused, retrying (repeated 49 times)",
+            ]
+        );
+        assert!(lines[2].contains(r#""level":"WARN""#), "{}", lines[2]);
+        assert!(lines[2].contains(r#""repeated":49"#), "{}", lines[2]);
+        assert!(
+            lines[2].contains(r#""target":"test_dedup""#),
+            "{}",
+            lines[2]
+        );
+        assert!(!log_args_runtime::dedup::is_active());
+    }
+
+    #[test]
+    fn test_different_messages_not_collapsed() {
+        let logs = capture_json(|| numbered_retries("db"));
+
+        let lines = logs.lines();
+        assert_eq!(
+            messages(&lines),
+            ["Attempt 0 failed", "Attempt 1 failed", "Attempt 2 failed"]
+        );
+        assert!(lines[2].contains(r#""attempt":2"#), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_window_and_context_scoping() {
+        let logs = capture_json(|| windowed("sync"));
+
+        let lines = logs.lines();
+        assert_eq!(
+            messages(&lines),
+            [
+                "Polling",
+                "Polling (repeated 1 times)",
+                "Polling",
+                "Child polling",
+                "Child polling",
+                "Child polling (repeated 1 times)",
+            ]
+        );
+        assert!(lines[0].contains(r#""phase":"poll""#), "{}", lines[0]);
+        assert!(
+            lines[1].contains(r#"\"job\": \"\\\"sync\\\"\""#),
+            "{}",
+            lines[1]
+        );
+        assert!(lines[5].contains(r#"\"shard\": \"1\""#), "{}", lines[5]);
+        assert!(lines[4].contains(r#"\"shard\": \"2\""#), "{}", lines[4]);
+    }
+
+    #[test]
+    fn test_many_fields() {
+        let logs = capture_json(|| many_fields(1, 2, 3, 4, 5, 6, 7, 8, 9, 10));
+
+        let lines = logs.lines();
+        assert_eq!(
+            messages(&lines),
+            ["Many fields", "Many fields (repeated 1 times)"]
+        );
+        assert!(
+            lines[0].contains(r#""j":"10""#) || lines[0].contains(r#""j":10"#),
+            "{}",
+            lines[0]
+        );
+    }
+
+    #[test]
+    fn test_follow_up_keeps_the_target() {
+        let logs = capture_json(targeted_retries);
+
+        let lines = logs.lines();
+        assert_eq!(
+            messages(&lines),
+            ["Backing off", "Backing off (repeated 2 times)"]
+        );
+        assert!(lines[1].contains(r#""target":"retry""#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_scope_stays_with_its_task() {
+        let logs = capture_json(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    tokio::join!(drain("jobs"), heartbeat(1));
+                    assert!(!log_args_runtime::dedup::is_active());
+                });
+        });
+
+        let lines = logs.lines();
+        let heartbeats = lines.iter().filter(|l| l.contains("Heartbeat")).count();
+        assert_eq!(heartbeats, 3, "{lines:?}");
+        let drained: Vec<_> = messages(&lines)
+            .into_iter()
+            .filter(|message| message.starts_with("Queue empty"))
+            .collect();
+        assert_eq!(drained, ["Queue empty", "Queue empty (repeated 2 times)"]);
+    }
+}
@@ -0,0 +1,133 @@
+//! Tests for `event!` and the span macros inside `#[params]` functions
+//!
+//! Tests that `event!` calls with an explicit level get the function's fields and context
+//! like `info!`, that `span!`, `info_span!` and friends get them as span fields, and that
+//! the body may import `event` and `span` itself
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+#[params(fields(order_id))]
+fn ship_order(order_id: u64) {
+    event!(Level::INFO, "Shipping");
+    tracing::event!(target: "warehouse", Level::WARN, stock = 0, "Low stock");
+    event!(name: "Shipped", Level::DEBUG, carrier = "ups");
+}
+
+#[params(min_level = warn, fields(queue))]
+fn poll_queue(queue: &str) {
+    event!(Level::DEBUG, "Polling");
+    event!(Level::ERROR, "Queue stalled");
+}
+
+#[params(span, fields(tenant))]
+fn handle_request(tenant: &str) {
+    let _request = tracing::info_span!("request").entered();
+    let _query = debug_span!(target: "db", "query", table = "orders").entered();
+    let _work = tracing::span!(Level::TRACE, "work").entered();
+}
+
+// Imports of `event` and `span` in the body name both the macro and the module
+#[params(span, fields(sku))]
+fn restock(sku: &str) -> Option<tracing::Id> {
+    use tracing::{event, span};
+    event!(Level::INFO, "Restocking");
+    let restock: span::Span = span!(Level::INFO, "restock");
+    restock.id()
+}
+
+/// Name and field names of a span
+type SpanFields = (String, Vec<String>);
+
+/// Layer recording the name and field names of each new span
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<SpanFields>>>);
+
+struct FieldNames(Vec<String>);
+
+impl Visit for FieldNames {
+    fn record_debug(&mut self, field: &Field, _value: &dyn std::fmt::Debug) {
+        self.0.push(field.name().to_string());
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Spans {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        let mut fields = FieldNames(vec![]);
+        attrs.record(&mut fields);
+        self.0
+            .lock()
+            .unwrap()
+            .push((attrs.metadata().name().to_string(), fields.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_gets_fields() {
+        let logs = capture_json(|| ship_order(7));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+            assert!(line.contains(r#""context""#), "{line}");
+        }
+        assert!(lines[0].contains(r#""level":"INFO""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""level":"WARN""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""target":"warehouse""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""stock":0"#), "{}", lines[1]);
+        assert!(lines[2].contains(r#""level":"DEBUG""#), "{}", lines[2]);
+        assert!(lines[2].contains(r#""carrier":"ups""#), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_event_respects_min_level() {
+        let logs = capture_json(|| poll_queue("jobs"));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("Queue stalled"), "{}", lines[0]);
+    }
+
+    #[test]
+    fn test_spans_get_fields() {
+        let spans = Spans::default();
+        let subscriber = tracing_subscriber::registry().with(spans.clone());
+        tracing::subscriber::with_default(subscriber, || handle_request("acme"));
+
+        let spans = spans.0.lock().unwrap().clone();
+        let names: Vec<_> = spans.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["request", "query", "work"]);
+        for (name, fields) in &spans {
+            assert!(
+                fields.contains(&"context".to_string()),
+                "{name}: {fields:?}"
+            );
+            assert!(fields.contains(&"tenant".to_string()), "{name}: {fields:?}");
+        }
+        assert!(spans[1].1.contains(&"table".to_string()));
+    }
+
+    #[test]
+    fn test_imported_event_and_span() {
+        let logs = capture_json(|| {
+            assert!(restock("A-1").is_some());
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 1, "{lines:?}");
+        assert!(lines[0].contains("Restocking"), "{}", lines[0]);
+        assert!(lines[0].contains("A-1"), "{}", lines[0]);
+    }
+}
@@ -0,0 +1,97 @@
+//! Tests for per-function event overrides
+//!
+//! Tests that `target = ...` and `name = ...` apply to every event logged inside the
+//! function, and that `min_level = ...` silences less severe events there only
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use std::sync::{Arc, Mutex};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+#[params(target = "billing", name = "ChargeCard", fields(order_id))]
+fn charge_card(order_id: u64) {
+    info!("Charging card");
+    warn!(retry = true, "Card declined");
+}
+
+#[params(min_level = warn, span, fields(queue))]
+fn poll_queue(queue: &str) {
+    trace!("Polling");
+    debug!(depth = 0, "Queue empty");
+    info!("Poll finished");
+    warn!("Queue is backing up");
+    drain_queue();
+}
+
+#[params(span)]
+fn drain_queue() {
+    debug!("Draining");
+}
+
+/// Layer recording the target and name of each event
+#[derive(Clone, Default)]
+struct Metadata(Arc<Mutex<Vec<(String, String)>>>);
+
+impl<S: Subscriber> Layer<S> for Metadata {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        self.0
+            .lock()
+            .unwrap()
+            .push((metadata.target().to_string(), metadata.name().to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_override() {
+        let logs = capture_json(|| charge_card(7));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(line.contains(r#""target":"billing""#), "{line}");
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+        }
+        assert!(lines[1].contains(r#""retry":true"#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_name_override() {
+        let metadata = Metadata::default();
+        let subscriber = tracing_subscriber::registry().with(metadata.clone());
+        tracing::subscriber::with_default(subscriber, || charge_card(7));
+
+        let events = metadata.0.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            [
+                ("billing".to_string(), "ChargeCard".to_string()),
+                ("billing".to_string(), "ChargeCard".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_min_level_silences_function_only() {
+        let logs = capture_json(|| poll_queue("jobs"));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("Queue is backing up"), "{}", lines[0]);
+        assert!(lines[0].contains(r#""level":"WARN""#), "{}", lines[0]);
+        // Child functions keep their own levels
+        assert!(lines[1].contains("Draining"), "{}", lines[1]);
+        assert!(
+            lines[1].contains(r#"\"queue\": \"\\\"jobs\\\"\""#),
+            "{}",
+            lines[1]
+        );
+    }
+}
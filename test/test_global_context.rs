@@ -0,0 +1,114 @@
+//! Tests for call-tree scoped custom fields and the explicit global context
+//!
+//! Tests that `custom(...)` and `auto_capture` do not leak across requests, that `global(...)`
+//! is evaluated once, and that the global store APIs behave as documented, including global
+//! fields only applying outside a call tree
+
+use log_args::params;
+use log_args_runtime::{get_context_value, remove_global_context, scoped_global_context};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+fn next_build() -> usize {
+    BUILDS.fetch_add(1, Ordering::SeqCst) + 1
+}
+
+#[params(span, custom(leak_tenant = tenant))]
+fn handle_request(tenant: &str) -> Option<String> {
+    get_context_value("leak_tenant")
+}
+
+#[params(global(deployment_id = "deploy-42"))]
+fn start_server() {
+    info!("Server starting");
+}
+
+#[params(global(build_number = next_build()))]
+fn deploy() {
+    info!("Deploying");
+    info!("Deployed");
+}
+
+#[params(span, auto_capture, fields(capture_tenant))]
+fn captured(capture_tenant: &str) -> Option<String> {
+    get_context_value("capture_tenant")
+}
+
+#[params(span, fields(job))]
+fn run_job(job: &str) -> bool {
+    log_args_runtime::get_context().contains_key("fallback_region")
+}
+
+fn on_other_thread(key: &'static str) -> Option<String> {
+    std::thread::spawn(move || get_context_value(key))
+        .join()
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_fields_scoped_to_call_tree() {
+        assert_eq!(handle_request("acme").as_deref(), Some("acme"));
+
+        // Neither this thread after return nor an unrelated thread sees request A's value
+        assert_eq!(get_context_value("leak_tenant"), None);
+        assert_eq!(on_other_thread("leak_tenant"), None);
+    }
+
+    #[test]
+    fn test_global_attribute_is_process_wide() {
+        start_server();
+        assert_eq!(
+            on_other_thread("deployment_id").as_deref(),
+            Some("deploy-42")
+        );
+        assert_eq!(
+            remove_global_context("deployment_id").as_deref(),
+            Some("deploy-42")
+        );
+        assert_eq!(on_other_thread("deployment_id"), None);
+    }
+
+    #[test]
+    fn test_scoped_global_override() {
+        log_args_runtime::set_global_context("scoped_region", "eu");
+        {
+            let _guard = scoped_global_context("scoped_region", "us");
+            assert_eq!(on_other_thread("scoped_region").as_deref(), Some("us"));
+        }
+        assert_eq!(on_other_thread("scoped_region").as_deref(), Some("eu"));
+
+        {
+            let _guard = scoped_global_context("scoped_only", "tmp");
+            assert_eq!(get_context_value("scoped_only").as_deref(), Some("tmp"));
+        }
+        assert_eq!(get_context_value("scoped_only"), None);
+        remove_global_context("scoped_region");
+    }
+
+    #[test]
+    fn test_global_expression_evaluated_once() {
+        deploy();
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 1);
+        assert_eq!(remove_global_context("build_number").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_auto_capture_does_not_publish_globally() {
+        assert!(captured("acme").is_some());
+        assert_eq!(get_context_value("capture_tenant"), None);
+        assert_eq!(on_other_thread("capture_tenant"), None);
+    }
+
+    #[test]
+    fn test_global_context_is_a_fallback() {
+        let _guard = scoped_global_context("fallback_region", "eu");
+        assert!(log_args_runtime::get_context().contains_key("fallback_region"));
+        // Inside a call tree only the call tree's fields are logged
+        assert!(!run_job("nightly"));
+    }
+}
@@ -0,0 +1,81 @@
+//! Tests for isolated context scopes
+//!
+//! Tests that `#[params(isolate)]` and `isolated_scope` hide parent and global context
+//! while the surrounding context is restored afterwards
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use log_args_runtime::{get_context_value, isolated_scope, scoped_global_context};
+
+#[params(span, custom(request_id = "req-1"))]
+fn handle_request() -> (Option<String>, Option<String>) {
+    let job = run_job(7);
+    (job, get_context_value("request_id"))
+}
+
+#[params(isolate, span, fields(job_id))]
+fn run_job(job_id: u64) -> Option<String> {
+    info!("Job started");
+    job_child();
+    get_context_value("request_id")
+}
+
+#[params(span)]
+fn job_child() {
+    info!("Job child");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isolated_function_hides_parent_context() {
+        let (inside, after) = handle_request();
+        assert_eq!(inside, None);
+        assert_eq!(after.as_deref(), Some("req-1"));
+    }
+
+    #[test]
+    fn test_isolated_function_logs_own_context_only() {
+        let logs = capture_json(|| {
+            handle_request();
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert!(line.contains("job_id"), "{line}");
+            assert!(!line.contains("req-1"), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_isolated_scope_hides_global_context() {
+        let _global = scoped_global_context("isolation_region", "eu");
+        let frame = log_args_runtime::push_context(
+            [("isolation_user".to_string(), "u-1".to_string())]
+                .into_iter()
+                .collect(),
+        );
+
+        let inside = isolated_scope(|| {
+            assert!(log_args_runtime::is_isolated());
+            (
+                get_context_value("isolation_region"),
+                get_context_value("isolation_user"),
+                log_args_runtime::get_context().contains_key("isolation_user"),
+            )
+        });
+        assert_eq!(inside, (None, None, false));
+
+        assert!(!log_args_runtime::is_isolated());
+        assert_eq!(get_context_value("isolation_user").as_deref(), Some("u-1"));
+        // Global context only applies outside a call tree
+        assert_eq!(get_context_value("isolation_region"), None);
+        drop(frame);
+        assert_eq!(get_context_value("isolation_region").as_deref(), Some("eu"));
+    }
+}
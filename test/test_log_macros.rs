@@ -0,0 +1,64 @@
+//! Tests for `log` crate macros inside `#[params]` functions
+//!
+//! Tests that with the `log-macros` feature, `log::info!` and friends are rewritten to the
+//! context-aware macros, including `target:`, key-values and `log::log!` with a level
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+
+#[params(span, fields(job_id))]
+fn run_job(job_id: u64) {
+    log::info!("Starting job {}", job_id);
+    log::warn!(target: "scheduler", "Job is late");
+    import_rows(3);
+}
+
+#[params(fields(rows))]
+fn import_rows(rows: usize) {
+    let file = "rows.csv";
+    log::debug!(file, batch = 1, path:? = "/tmp"; "Imported {} rows", rows);
+    log::log!(log::Level::Error, "Import finished with errors");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_macros_get_fields_and_context() {
+        let logs = capture_json(|| run_job(9));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        assert!(lines[0].contains("Starting job 9"), "{}", lines[0]);
+        assert!(lines[0].contains(r#""job_id":"9""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""level":"WARN""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""target":"scheduler""#), "{}", lines[1]);
+        // Child functions inherit the context like with `info!`
+        for line in &lines[2..] {
+            assert!(line.contains(r#"\"job_id\": \"9\""#), "{line}");
+            assert!(line.contains(r#""rows":"3""#), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_key_values_and_level() {
+        let logs = capture_json(|| import_rows(3));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains(r#""level":"DEBUG""#), "{}", lines[0]);
+        assert!(lines[0].contains("Imported 3 rows"), "{}", lines[0]);
+        assert!(lines[0].contains(r#""file":"rows.csv""#), "{}", lines[0]);
+        assert!(lines[0].contains(r#""batch":1"#), "{}", lines[0]);
+        assert!(lines[0].contains(r#""path":"\"/tmp\"""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""level":"ERROR""#), "{}", lines[1]);
+        assert!(
+            lines[1].contains("Import finished with errors"),
+            "{}",
+            lines[1]
+        );
+    }
+}
@@ -0,0 +1,152 @@
+//! Tests for tracing's `name:`, `target:` and `parent:` prefixes in rewritten macros
+//!
+//! Tests that every prefix combination keeps working inside `#[params]` functions, with
+//! the function's fields spliced after the prefix
+
+use log_args::params;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// What a [`Recorder`] saw of one event
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Seen {
+    target: String,
+    name: String,
+    parent: Option<String>,
+    fields: Vec<String>,
+}
+
+/// Layer recording target, name, parent span and field names of each event
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<Seen>>>);
+
+struct FieldNames(Vec<String>);
+
+impl Visit for FieldNames {
+    fn record_debug(&mut self, field: &Field, _value: &dyn std::fmt::Debug) {
+        self.0.push(field.name().to_string());
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = FieldNames(vec![]);
+        event.record(&mut fields);
+        self.0.lock().unwrap().push(Seen {
+            target: event.metadata().target().to_string(),
+            name: event.metadata().name().to_string(),
+            parent: ctx.event_span(event).map(|span| span.name().to_string()),
+            fields: fields.0,
+        });
+    }
+}
+
+fn record<F: FnOnce()>(f: F) -> Vec<Seen> {
+    let recorder = Recorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    let seen = recorder.0.lock().unwrap().clone();
+    seen
+}
+
+#[params(fields(order_id))]
+fn every_prefix(order_id: u64) {
+    let span = tracing::info_span!("request");
+    info!(target: "db", "target only");
+    warn!(name: "Named", "name only");
+    error!(parent: &span, "parent only");
+    debug!(name: "Named", target: "db", "name and target");
+    trace!(target: "db", parent: &span, "target and parent");
+    info!(name: "Named", parent: &span, "name and parent");
+    info!(name: "Named", target: "db", parent: &span, rows = 3, "all prefixes");
+    info!(target: "db", rows = 3);
+}
+
+#[params(target = "billing", fields(order_id))]
+fn statement_overrides_function(order_id: u64) {
+    info!("function target");
+    info!(target: "db", "statement target");
+}
+
+#[params(dedup, fields(order_id))]
+fn deduplicated(order_id: u64) {
+    for _ in 0..3 {
+        warn!(target: "db", "retrying");
+    }
+}
+
+fn unannotated() {
+    log_args_runtime::info!(target: "db", name: "Plain", "outside params");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_prefix_form() {
+        let events = record(|| every_prefix(7));
+
+        let module = module_path!().trim_end_matches("::tests");
+        let summary: Vec<_> = events
+            .iter()
+            .map(|event| {
+                (
+                    event.target.as_str(),
+                    event.name == "Named",
+                    event.parent.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("db", false, None),
+                (module, true, None),
+                (module, false, Some("request")),
+                ("db", true, None),
+                ("db", false, Some("request")),
+                (module, true, Some("request")),
+                ("db", true, Some("request")),
+                ("db", false, None),
+            ]
+        );
+        for event in &events {
+            assert!(event.fields.contains(&"order_id".to_string()), "{event:?}");
+            assert!(event.fields.contains(&"context".to_string()), "{event:?}");
+        }
+        assert!(events[6].fields.contains(&"rows".to_string()));
+        assert!(events[6].fields.contains(&"message".to_string()));
+        assert!(!events[7].fields.contains(&"message".to_string()));
+    }
+
+    #[test]
+    fn test_statement_prefix_overrides_function() {
+        let events = record(|| statement_overrides_function(7));
+
+        let targets: Vec<_> = events.iter().map(|event| event.target.as_str()).collect();
+        assert_eq!(targets, ["billing", "db"]);
+    }
+
+    #[test]
+    fn test_prefix_with_dedup() {
+        let events = record(|| deduplicated(7));
+
+        assert_eq!(events.len(), 2, "{events:?}");
+        assert_eq!(events[0].target, "db");
+        assert!(events[0].fields.contains(&"order_id".to_string()));
+        assert!(events[1].fields.contains(&"repeated".to_string()));
+    }
+
+    #[test]
+    fn test_prefix_outside_params() {
+        let events = record(unannotated);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].target, "db");
+        assert_eq!(events[0].name, "Plain");
+    }
+}
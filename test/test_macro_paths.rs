@@ -0,0 +1,90 @@
+//! Tests for log macros called by path, through aliases or through wrappers
+//!
+//! Tests that `::tracing::info!`, `log_args_runtime::info!`, aliases imported in the body
+//! and wrapper macros listed in `macros(...)` all get the function's fields and context
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+
+macro_rules! my_log {
+    ($($t:tt)*) => {
+        tracing::info!(wrapper = "my_log", $($t)*)
+    };
+}
+
+macro_rules! audit_log {
+    ($($t:tt)*) => {
+        tracing::warn!(target: "audit_log", $($t)*)
+    };
+}
+
+#[params(fields(order_id))]
+fn qualified_paths(order_id: u64) {
+    ::tracing::info!("Leading colons");
+    log_args_runtime::warn!("Runtime path");
+    ::log_args_runtime::error!(target: "orders", "Runtime path with prefix");
+    let _span = ::tracing::info_span!("work").entered();
+}
+
+#[params(fields(order_id))]
+fn aliased(order_id: u64) {
+    use tracing::info as tinfo;
+    use tracing::{debug, warn as twarn};
+
+    tinfo!("Through an alias");
+    twarn!(retries = 2, "Through a grouped alias");
+    debug!("Not aliased");
+}
+
+#[params(macros(my_log, audit_log), fields(order_id))]
+fn wrapped(order_id: u64) {
+    my_log!("Through a wrapper");
+    audit_log!(actor = "alice", "Through a wrapper with a target");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualified_paths() {
+        let logs = capture_json(|| qualified_paths(7));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+        }
+        assert!(lines[2].contains(r#""target":"orders""#), "{}", lines[2]);
+    }
+
+    #[test]
+    fn test_aliases() {
+        let logs = capture_json(|| aliased(7));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 3, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+        }
+        assert!(lines[1].contains(r#""level":"WARN""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""retries":2"#), "{}", lines[1]);
+    }
+
+    #[test]
+    fn test_wrapper_macros() {
+        let logs = capture_json(|| wrapped(7));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+            assert!(line.contains(r#""context""#), "{line}");
+        }
+        assert!(lines[0].contains(r#""wrapper":"my_log""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""target":"audit_log""#), "{}", lines[1]);
+        assert!(lines[1].contains(r#""actor":"alice""#), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Tests for per-function metrics
+//!
+//! Tests that `#[params(metrics)]` records calls, errors, in-flight calls and durations
+//! through the `metrics` facade, with labels from `fields`/`span` and a cardinality guard
+
+use log_args::params;
+use log_args_runtime::metrics::{
+    CALLS_TOTAL, DURATION_SECONDS, ERRORS_TOTAL, IN_FLIGHT, OVERFLOW_LABEL_VALUE,
+};
+use metrics::{SharedString, Unit};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+use metrics_util::CompositeKey;
+
+#[params(metrics(labels(tenant_id)), fields(tenant_id, amount))]
+fn charge(tenant_id: &str, amount: u64) -> Result<(), String> {
+    if amount == 0 {
+        return Err("empty charge".to_string());
+    }
+    info!("Charging");
+    Ok(())
+}
+
+// Snapshots taken inside the call see it in flight
+#[params(metrics)]
+fn in_flight(snapshotter: &Snapshotter) -> Metrics {
+    snapshotter.snapshot().into_vec()
+}
+
+#[params(metrics(labels(user_id), max_label_values = 2), fields(user_id))]
+fn lookup(user_id: u64) {}
+
+#[params(span, fields(tenant))]
+fn handle_request(tenant: &str) {
+    process_order();
+}
+
+#[params(span(tenant), metrics(labels(tenant)))]
+fn process_order() {}
+
+// Snapshots drain the recorder, so each test takes one and looks metrics up in it
+type Metrics = Vec<(CompositeKey, Option<Unit>, Option<SharedString>, DebugValue)>;
+
+/// Value of the metric `name` whose labels include all of `labels`
+fn find<'a>(metrics: &'a Metrics, name: &str, labels: &[(&str, &str)]) -> Option<&'a DebugValue> {
+    metrics
+        .iter()
+        .find(|(key, _, _, _)| {
+            let key = key.key();
+            key.name() == name
+                && labels.iter().all(|(label, value)| {
+                    key.labels()
+                        .any(|l| l.key() == *label && l.value() == *value)
+                })
+        })
+        .map(|(_, _, _, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_calls_errors_and_durations() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            charge("acme", 10).unwrap();
+            charge("acme", 20).unwrap();
+            charge("acme", 0).unwrap_err();
+        });
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let labels = [("function", "charge"), ("tenant_id", "acme")];
+        assert_eq!(
+            find(&metrics, CALLS_TOTAL, &labels),
+            Some(&DebugValue::Counter(3))
+        );
+        assert_eq!(
+            find(&metrics, ERRORS_TOTAL, &labels),
+            Some(&DebugValue::Counter(1))
+        );
+        match find(&metrics, DURATION_SECONDS, &labels) {
+            Some(DebugValue::Histogram(durations)) => assert_eq!(durations.len(), 3),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_in_flight_gauge() {
+        let labels = [("function", "in_flight")];
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let during = metrics::with_local_recorder(&recorder, || in_flight(&snapshotter));
+        assert_eq!(
+            find(&during, IN_FLIGHT, &labels),
+            Some(&DebugValue::Gauge(1.0.into()))
+        );
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            in_flight(&DebuggingRecorder::new().snapshotter())
+        });
+        let after = snapshotter.snapshot().into_vec();
+        assert_eq!(
+            find(&after, IN_FLIGHT, &labels),
+            Some(&DebugValue::Gauge(0.0.into()))
+        );
+        assert_eq!(
+            find(&after, CALLS_TOTAL, &labels),
+            Some(&DebugValue::Counter(1))
+        );
+        assert_eq!(find(&after, ERRORS_TOTAL, &labels), None);
+    }
+
+    #[test]
+    fn test_label_cardinality_guard() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            for user_id in [1, 2, 3, 4, 1] {
+                lookup(user_id);
+            }
+        });
+
+        let metrics = snapshotter.snapshot().into_vec();
+        let calls = |user_id: &str| {
+            find(
+                &metrics,
+                CALLS_TOTAL,
+                &[("function", "lookup"), ("user_id", user_id)],
+            )
+        };
+        assert_eq!(calls("1"), Some(&DebugValue::Counter(2)));
+        assert_eq!(calls("2"), Some(&DebugValue::Counter(1)));
+        assert_eq!(calls("3"), None);
+        assert_eq!(calls(OVERFLOW_LABEL_VALUE), Some(&DebugValue::Counter(2)));
+    }
+
+    #[test]
+    fn test_span_label_from_parent_context() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || handle_request("acme"));
+
+        let metrics = snapshotter.snapshot().into_vec();
+        assert_eq!(
+            find(
+                &metrics,
+                CALLS_TOTAL,
+                &[("function", "process_order"), ("tenant", "acme")]
+            ),
+            Some(&DebugValue::Counter(1))
+        );
+    }
+}
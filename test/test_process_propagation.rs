@@ -0,0 +1,67 @@
+//! Tests for child-process context propagation
+//!
+//! Tests exporting context as environment variables and seeding the child's root frame
+
+use log_args::params;
+use log_args_runtime::CommandExt;
+use std::process::Command;
+
+const CHILD: &str = env!("CARGO_BIN_EXE_ctx_child");
+
+// Parent shells out to a worker binary from inside a function with span context
+#[params(span, custom(request_id = "req-7", tenant_id = "acme"))]
+fn run_worker() -> String {
+    let output = Command::new(CHILD)
+        .with_log_context()
+        .output()
+        .expect("failed to run child");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[params(span, custom(request_id = "req-8"))]
+fn run_worker_with_prefix(prefix: &str) -> String {
+    let output = Command::new(CHILD)
+        .arg(prefix)
+        .with_log_context_prefix(prefix)
+        .output()
+        .expect("failed to run child");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_inherits_parent_context() {
+        let output = run_worker();
+        assert_eq!(output, "request_id=req-7\ntenant_id=acme\n");
+    }
+
+    #[test]
+    fn test_custom_prefix() {
+        let output = run_worker_with_prefix("MY_APP_CTX_");
+        assert_eq!(output, "request_id=req-8\n");
+    }
+
+    #[test]
+    fn test_child_without_context() {
+        let output = Command::new(CHILD)
+            .env_remove("LOG_ARGS_CTX_request_id")
+            .output()
+            .expect("failed to run child");
+        assert!(output.stdout.is_empty());
+    }
+
+    #[test]
+    fn test_context_to_env_names() {
+        let mut fields = log_args_runtime::ContextFrame::new();
+        fields.insert("request_id".to_string(), "req-1".to_string());
+        fields.insert("bad=key".to_string(), "x".to_string());
+        let vars = log_args_runtime::process::context_to_env(&fields.into(), "LOG_ARGS_CTX_");
+        assert_eq!(
+            vars,
+            vec![("LOG_ARGS_CTX_request_id".to_string(), "req-1".to_string())]
+        );
+    }
+}
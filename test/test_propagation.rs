@@ -0,0 +1,195 @@
+//! Tests for W3C baggage/traceparent context propagation
+//!
+//! Tests injecting context into headers and extracting it on the receiving side
+
+use log_args::params;
+use log_args_runtime::propagation::{self, PropagationConfig};
+use log_args_runtime::ContextFrame;
+use std::collections::HashMap;
+
+// Outgoing call made from inside a function with span context
+#[params(span, fields(request_id, tenant_id))]
+fn outgoing_call(
+    request_id: String,
+    tenant_id: String,
+    _password: String,
+) -> HashMap<String, String> {
+    let config = PropagationConfig::new(["request_id", "tenant_id"]);
+    let mut headers = HashMap::new();
+    propagation::inject(&mut headers, &config);
+    headers
+}
+
+// Outgoing call with `traceparent` from a function that has no trace context yet
+#[params(span, fields(request_id))]
+fn traced_call(request_id: String) -> (HashMap<String, String>, HashMap<String, String>) {
+    let config = PropagationConfig::new(["request_id"]).with_traceparent(true);
+    let mut first = HashMap::new();
+    propagation::inject(&mut first, &config);
+    let mut second = HashMap::new();
+    propagation::inject(&mut second, &config);
+    let trace_id = log_args_runtime::get_context_value(propagation::TRACE_ID_KEY).unwrap();
+    let span_id = log_args_runtime::get_context_value(propagation::SPAN_ID_KEY).unwrap();
+    assert_eq!(
+        first[propagation::TRACEPARENT_HEADER],
+        format!("00-{trace_id}-{span_id}-01")
+    );
+    (first, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PropagationConfig {
+        PropagationConfig::new(["request_id", "tenant_id"])
+    }
+
+    #[test]
+    fn test_inject_from_current_context() {
+        let headers = outgoing_call(
+            "req-1".to_string(),
+            "acme".to_string(),
+            "hunter2".to_string(),
+        );
+
+        let baggage = &headers[propagation::BAGGAGE_HEADER];
+        assert!(baggage.contains("request_id=%22req-1%22"));
+        assert!(baggage.contains("tenant_id=%22acme%22"));
+        assert!(!baggage.contains("hunter2"));
+        assert!(!headers.contains_key(propagation::TRACEPARENT_HEADER));
+    }
+
+    #[test]
+    fn test_round_trip_through_headers() {
+        let mut context = ContextFrame::new();
+        context.insert("request_id".to_string(), "req 42, ok;=%".to_string());
+        context.insert("tenant_id".to_string(), "acme".to_string());
+
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(&context.clone().into(), &mut headers, &config());
+        let extracted = propagation::extract(&headers, &config());
+
+        assert_eq!(extracted, context);
+    }
+
+    #[test]
+    fn test_allowlist_applies_on_both_sides() {
+        let mut context = ContextFrame::new();
+        context.insert("request_id".to_string(), "req-1".to_string());
+        context.insert("session_token".to_string(), "secret".to_string());
+
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(&context.clone().into(), &mut headers, &config());
+        assert_eq!(headers[propagation::BAGGAGE_HEADER], "request_id=req-1");
+
+        let mut incoming = HashMap::new();
+        incoming.insert(
+            "Baggage".to_string(),
+            "session_token=secret, request_id=req-2;prop=1".to_string(),
+        );
+        let extracted = propagation::extract(&incoming, &config());
+        assert_eq!(extracted.len(), 1);
+        assert_eq!(extracted["request_id"], "req-2");
+    }
+
+    #[test]
+    fn test_size_limits() {
+        let mut context = ContextFrame::new();
+        context.insert("request_id".to_string(), "r".repeat(64));
+        context.insert("tenant_id".to_string(), "acme".to_string());
+
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(
+            &context.clone().into(),
+            &mut headers,
+            &config().max_bytes(32),
+        );
+        assert_eq!(headers[propagation::BAGGAGE_HEADER], "tenant_id=acme");
+
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(
+            &context.clone().into(),
+            &mut headers,
+            &config().max_entries(1),
+        );
+        assert!(headers[propagation::BAGGAGE_HEADER].starts_with("request_id="));
+
+        let mut incoming = HashMap::new();
+        incoming.insert(
+            propagation::BAGGAGE_HEADER.to_string(),
+            "request_id=a,tenant_id=b".to_string(),
+        );
+        let extracted = propagation::extract(&incoming, &config().max_entries(1));
+        assert_eq!(extracted.len(), 1);
+    }
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let config = config().with_traceparent(true);
+        let mut context = ContextFrame::new();
+        context.insert(
+            propagation::TRACE_ID_KEY.to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+        );
+        context.insert(
+            propagation::SPAN_ID_KEY.to_string(),
+            "00f067aa0ba902b7".to_string(),
+        );
+
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(&context.clone().into(), &mut headers, &config);
+        assert_eq!(
+            headers[propagation::TRACEPARENT_HEADER],
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+
+        let extracted = propagation::extract(&headers, &config);
+        assert_eq!(
+            extracted[propagation::TRACE_ID_KEY],
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(extracted[propagation::PARENT_ID_KEY], "00f067aa0ba902b7");
+    }
+
+    #[test]
+    fn test_traceparent_generated_into_context_and_validated() {
+        let config = config().with_traceparent(true);
+        let mut headers = HashMap::new();
+        propagation::inject_snapshot(&Default::default(), &mut headers, &config);
+        propagation::inject(&mut headers, &config);
+        assert!(!headers.contains_key(propagation::TRACEPARENT_HEADER));
+
+        // Generated ids are recorded, so every request and log line shares them
+        let (first, second) = traced_call("req-1".to_string());
+        let traceparent = &first[propagation::TRACEPARENT_HEADER];
+        assert_eq!(traceparent.len(), 55);
+        assert_eq!(traceparent, &second[propagation::TRACEPARENT_HEADER]);
+        assert!(propagation::extract(&first, &config).contains_key(propagation::TRACE_ID_KEY));
+
+        let mut incoming = HashMap::new();
+        incoming.insert(
+            propagation::TRACEPARENT_HEADER.to_string(),
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01".to_string(),
+        );
+        assert!(propagation::extract(&incoming, &config).is_empty());
+    }
+
+    #[test]
+    fn test_extract_and_push() {
+        let mut incoming = HashMap::new();
+        incoming.insert(
+            propagation::BAGGAGE_HEADER.to_string(),
+            "request_id=req-9".to_string(),
+        );
+
+        {
+            let _guard = propagation::extract_and_push(&incoming, &config());
+            assert_eq!(
+                log_args_runtime::get_context_value("request_id").as_deref(),
+                Some("req-9")
+            );
+        }
+        assert_eq!(log_args_runtime::get_context().get("request_id"), None);
+    }
+}
@@ -0,0 +1,87 @@
+//! Tests for recording context fields mid-function
+//!
+//! Tests that `record!` adds fields to the current frame for later log calls and
+//! child functions, in both sync and async code
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use log_args_runtime::get_context_value;
+
+fn find_user(email: &str) -> u64 {
+    email.len() as u64
+}
+
+#[params(span, fields(email))]
+fn login(email: &str) -> Option<String> {
+    info!("Looking up user");
+    let user_id = find_user(email);
+    log_args_runtime::record!(user_id = user_id, lookup = ?"by_email");
+    info!("User found");
+    load_profile()
+}
+
+#[params(span)]
+fn load_profile() -> Option<String> {
+    info!("Loading profile");
+    get_context_value("user_id")
+}
+
+#[params(span, fields(email))]
+async fn login_async(email: String) -> Option<String> {
+    tokio::task::yield_now().await;
+    log_args_runtime::record!(user_id = find_user(&email));
+    load_profile_async().await
+}
+
+#[params(span)]
+async fn load_profile_async() -> Option<String> {
+    info!("Loading profile");
+    get_context_value("user_id")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_visible_to_later_logs_and_children() {
+        let logs = capture_json(|| {
+            assert_eq!(login("a@example.com").as_deref(), Some("13"));
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 3);
+        assert!(!lines[0].contains("user_id"), "{}", lines[0]);
+        for line in &lines[1..] {
+            assert!(line.contains(r#"\"user_id\": \"13\""#), "{line}");
+            assert!(
+                line.contains(r#"\"lookup\": \"\\\"by_email\\\"\""#),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_record_scoped_to_frame() {
+        login("a@example.com");
+        assert_eq!(get_context_value("user_id"), None);
+    }
+
+    #[test]
+    fn test_record_outside_frame_is_noop() {
+        assert!(!log_args_runtime::record_context(
+            "user_id",
+            "1".to_string()
+        ));
+        assert_eq!(get_context_value("user_id"), None);
+    }
+
+    #[tokio::test]
+    async fn test_record_in_async_function() {
+        let user_id = login_async("b@example.com".to_string()).await;
+        assert_eq!(user_id.as_deref(), Some("13"));
+        assert_eq!(get_context_value("user_id"), None);
+    }
+}
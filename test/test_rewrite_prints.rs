@@ -0,0 +1,64 @@
+//! Tests for `rewrite_prints`
+//!
+//! Tests that in debug builds `dbg!`, `println!` and `eprintln!` inside a
+//! `#[params(rewrite_prints)]` function become `debug!` events with the function's fields,
+//! and that `dbg!` still returns its argument
+
+#![cfg(debug_assertions)]
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+
+#[params(rewrite_prints, fields(order_id))]
+fn total(order_id: u64, items: &[u64]) -> u64 {
+    let count = items.len();
+    println!("{count} items");
+    eprintln!("Summing");
+    println!();
+    dbg!(items.iter().sum::<u64>())
+}
+
+#[params(rewrite_prints)]
+fn pair(a: u32, b: u32) -> (u32, u32) {
+    dbg!(a, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prints_become_debug_events() {
+        let mut result = 0;
+        let logs = capture_json(|| result = total(7, &[1, 2, 3]));
+
+        assert_eq!(result, 6);
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 4, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""level":"DEBUG""#), "{line}");
+            assert!(line.contains(r#""order_id":"7""#), "{line}");
+        }
+        assert!(lines[0].contains(r#""message":"3 items""#), "{}", lines[0]);
+        assert!(lines[1].contains(r#""message":"Summing""#), "{}", lines[1]);
+        assert!(
+            lines[3].contains("test/test_rewrite_prints.rs:20:5] items.iter().sum::<u64>() = 6"),
+            "{}",
+            lines[3]
+        );
+    }
+
+    #[test]
+    fn test_dbg_with_several_values() {
+        let mut result = (0, 0);
+        let logs = capture_json(|| result = pair(1, 2));
+
+        assert_eq!(result, (1, 2));
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("a = 1"), "{}", lines[0]);
+        assert!(lines[1].contains("b = 2"), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,157 @@
+//! Tests for per-call sampling and rate limiting
+//!
+//! Tests that `sample` and `rate_limit` keep or drop all lines of one invocation
+//! together, that children and spawned tasks inherit the decision and that suppressed
+//! events are reported when their window ends
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use std::time::Duration;
+
+#[params(sample = 0.0, fields(id))]
+fn never_sampled(id: u64) {
+    info!("Dropped");
+    sampled_child(id);
+}
+
+#[params(sample = 1.0, fields(id))]
+fn always_sampled(id: u64) {
+    info!("Kept");
+    sampled_child(id);
+}
+
+// Inherits the parent's decision instead of rolling its own
+#[params(sample = 0.0, fields(id))]
+fn sampled_child(id: u64) {
+    info!("Child");
+}
+
+#[params(sample = 0, fields(id))]
+async fn never_sampled_async(id: u64) {
+    info!("Dropped async");
+    tokio::spawn(spawned_child(id)).await.unwrap();
+}
+
+#[params(sample = 1, fields(id))]
+async fn spawned_child(id: u64) {
+    info!("Spawned child");
+}
+
+#[params(sample = 0.5, fields(id))]
+fn coin_flip(id: u64) {
+    info!("First line");
+    info!("Second line");
+}
+
+#[params(rate_limit = "5/s", fields(id))]
+fn hot_path(id: u64) {
+    info!("Hot path");
+    debug!("Hot path details");
+}
+
+#[params(sample = 1)]
+fn integer_rate() {
+    info!("Kept with an integer rate");
+}
+
+#[params(rate_limit = "2/s", fields(id))]
+fn burst(id: u64) {
+    info!("Burst");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampling_decision_propagates_to_children() {
+        let logs = capture_json(|| {
+            never_sampled(1);
+            always_sampled(2);
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].contains("Kept"));
+        assert!(lines[1].contains("Child"));
+    }
+
+    #[test]
+    fn test_all_lines_of_a_call_kept_or_dropped_together() {
+        let logs = capture_json(|| {
+            for id in 0..200 {
+                coin_flip(id);
+            }
+        });
+
+        let lines = logs.lines();
+        assert!(lines.len() > 40 && lines.len() < 360, "{}", lines.len());
+        for pair in lines.chunks(2) {
+            assert!(pair[0].contains("First line"));
+            assert!(pair[1].contains("Second line"));
+            let id = |line: &str| line.split(r#""id":"#).nth(1).unwrap().to_string();
+            assert_eq!(id(&pair[0]), id(&pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_rate_limit_suppresses_and_reports() {
+        let logs = capture_json(|| {
+            for id in 0..20 {
+                hot_path(id);
+            }
+            std::thread::sleep(Duration::from_millis(1100));
+            hot_path(20);
+        });
+
+        let lines = logs.lines();
+        let kept = lines.iter().filter(|l| l.contains("Hot path")).count();
+        assert_eq!(kept, 12, "{lines:?}");
+
+        let report = lines
+            .iter()
+            .find(|l| l.contains("Rate limit suppressed events"))
+            .expect("no suppression report");
+        assert!(report.contains(r#""function":"hot_path""#), "{report}");
+        assert!(report.contains(r#""suppressed":30"#), "{report}");
+    }
+
+    #[test]
+    fn test_spawned_tasks_inherit_the_decision() {
+        let logs = capture_json(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(never_sampled_async(1));
+        });
+
+        assert!(logs.lines().is_empty(), "{:?}", logs.lines());
+    }
+
+    #[test]
+    fn test_integer_rates() {
+        let logs = capture_json(integer_rate);
+
+        assert_eq!(logs.lines().len(), 1, "{:?}", logs.lines());
+    }
+
+    #[test]
+    fn test_report_when_traffic_stops() {
+        let logs = capture_json(|| {
+            for id in 0..5 {
+                burst(id);
+            }
+            std::thread::sleep(Duration::from_millis(1300));
+        });
+
+        let lines = logs.lines();
+        let report = lines
+            .iter()
+            .find(|l| l.contains("Rate limit suppressed events"))
+            .expect("no suppression report");
+        assert!(report.contains(r#""function":"burst""#), "{report}");
+        assert!(report.contains(r#""suppressed":3"#), "{report}");
+    }
+}
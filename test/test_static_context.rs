@@ -0,0 +1,76 @@
+//! Tests for startup-time static context
+//!
+//! Tests that process-wide fields declared once at startup reach every log event
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+use std::sync::Once;
+
+#[params(span, fields(order_id))]
+fn handle_order(order_id: u64) {
+    info!("Handling order");
+}
+
+#[params]
+fn background_tick() {
+    info!("Tick");
+}
+
+fn init_static() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        std::env::set_var("LOG_ARGS_STATIC_REGION", "eu-west-1");
+        let mut fields = log_args_runtime::static_context_from_env();
+        fields.insert("service".to_string(), "billing".to_string());
+        fields.insert("version".to_string(), "1.2.3".to_string());
+        assert!(log_args_runtime::init_static_context(fields));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_static_context_in_every_event() {
+        init_static();
+        let logs = capture_json(|| {
+            handle_order(7);
+            background_tick();
+            log_args_runtime::info!("Outside any function");
+        });
+
+        let output = logs.contents();
+        assert_eq!(output.lines().count(), 3);
+        for line in output.lines() {
+            assert!(line.contains(r#"\"service\": \"billing\""#), "{line}");
+            assert!(line.contains(r#"\"region\": \"eu-west-1\""#), "{line}");
+        }
+    }
+
+    #[test]
+    fn test_static_context_is_immutable() {
+        init_static();
+        assert!(!log_args_runtime::init_static_context([(
+            "service", "other"
+        )]));
+        assert!(!log_args_runtime::init_static_context_from_env());
+        assert_eq!(log_args_runtime::static_context()["service"], "billing");
+    }
+
+    #[test]
+    fn test_static_context_lookup() {
+        init_static();
+        assert_eq!(
+            log_args_runtime::get_context_value("version").as_deref(),
+            Some("1.2.3")
+        );
+        assert_eq!(
+            log_args_runtime::get_inherited_fields_map()["region"],
+            "eu-west-1"
+        );
+        assert!(log_args_runtime::get_inherited_context_string().contains("service=billing"));
+    }
+}
@@ -0,0 +1,64 @@
+//! Tests for context frames of async tasks
+//!
+//! Tests that frames pushed by async functions stay with their task across `.await`,
+//! that spawned tasks inherit the spawning function's frames and that dropping a
+//! pending future only pops its own frames
+
+use log_args::params;
+use log_args_runtime::get_context_value;
+
+#[params(span, fields(task_name))]
+async fn named_task(task_name: &'static str) -> Option<String> {
+    tokio::task::yield_now().await;
+    get_context_value("task_name")
+}
+
+#[params(span, fields(parent_id))]
+async fn spawn_child(parent_id: u64) -> Option<String> {
+    tokio::spawn(async { get_context_value("parent_id") })
+        .await
+        .unwrap()
+}
+
+#[params(span, fields(pending_id))]
+async fn never_finishes(pending_id: u64) {
+    std::future::pending::<()>().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use std::task::{Context, Waker};
+
+    #[tokio::test]
+    async fn test_frames_stay_with_their_task() {
+        let (first, second) = tokio::join!(named_task("first"), named_task("second"));
+
+        assert_eq!(first.as_deref(), Some("\"first\""));
+        assert_eq!(second.as_deref(), Some("\"second\""));
+        assert_eq!(get_context_value("task_name"), None);
+    }
+
+    #[tokio::test]
+    async fn test_spawned_task_inherits_frames() {
+        assert_eq!(spawn_child(7).await.as_deref(), Some("7"));
+    }
+
+    #[test]
+    fn test_dropped_future_pops_only_its_frames() {
+        let _frame = log_args_runtime::push_context(
+            [("request_id".to_string(), "req-1".to_string())]
+                .into_iter()
+                .collect(),
+        );
+
+        let mut pending = Box::pin(never_finishes(1));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert!(pending.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(get_context_value("pending_id"), None);
+        drop(pending);
+
+        assert_eq!(get_context_value("request_id").as_deref(), Some("req-1"));
+    }
+}
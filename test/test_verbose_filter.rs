@@ -0,0 +1,132 @@
+//! Tests for per-request log level elevation
+//!
+//! Tests that `#[params(verbose_if = ...)]` with `VerboseFilter` enables debug and trace
+//! events only for marked call trees, including their spawned tasks, up to the filter's
+//! maximum level
+
+mod common;
+
+use common::CapturedLogs;
+use log_args::params;
+use log_args_runtime::filter::VerboseFilter;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::Filter;
+use tracing_subscriber::prelude::*;
+
+struct User {
+    id: u64,
+    is_beta: bool,
+}
+
+#[params(verbose_if = user.is_beta, fields(user.id))]
+fn handle(user: User) {
+    info!("Handling request");
+    debug!("Request details");
+    load_settings();
+}
+
+#[params(span)]
+fn load_settings() {
+    trace!("Loading settings");
+}
+
+#[params(verbose_if = true)]
+async fn handle_async() {
+    tokio::spawn(async {
+        debug!("Spawned details");
+    })
+    .await
+    .unwrap();
+}
+
+fn capture_filtered<F: FnOnce()>(f: F) -> CapturedLogs {
+    capture_with(VerboseFilter::new(LevelFilter::INFO), f)
+}
+
+fn capture_with<F: FnOnce()>(filter: VerboseFilter<LevelFilter>, f: F) -> CapturedLogs {
+    let logs = CapturedLogs::default();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(logs.clone())
+        .with_filter(filter);
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), f);
+    logs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbose_call_tree_gets_debug_and_trace() {
+        let logs = capture_filtered(|| {
+            handle(User {
+                id: 1,
+                is_beta: true,
+            })
+        });
+
+        let output = logs.contents();
+        assert_eq!(output.lines().count(), 3, "{output}");
+        assert!(output.contains("Request details"));
+        assert!(output.contains("Loading settings"));
+        assert!(!log_args_runtime::filter::is_verbose());
+    }
+
+    #[test]
+    fn test_normal_filtering_for_everyone_else() {
+        let logs = capture_filtered(|| {
+            handle(User {
+                id: 2,
+                is_beta: false,
+            });
+            log_args_runtime::debug!("Outside any request");
+        });
+
+        let output = logs.contents();
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.contains("Handling request"));
+    }
+
+    #[test]
+    fn test_manual_mark() {
+        let logs = capture_filtered(|| {
+            let _verbose = log_args_runtime::filter::mark_verbose(true);
+            load_settings();
+        });
+
+        assert!(logs.contents().contains("Loading settings"));
+    }
+
+    #[test]
+    fn test_spawned_tasks_stay_verbose() {
+        let logs = capture_filtered(|| {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(handle_async());
+        });
+
+        assert!(logs.contents().contains("Spawned details"));
+    }
+
+    #[test]
+    fn test_max_level_caps_elevation_and_hint() {
+        let filter = VerboseFilter::new(LevelFilter::INFO).with_max_level(LevelFilter::DEBUG);
+        assert_eq!(
+            Filter::<tracing_subscriber::Registry>::max_level_hint(&filter),
+            Some(LevelFilter::DEBUG)
+        );
+
+        let logs = capture_with(filter, || {
+            handle(User {
+                id: 3,
+                is_beta: true,
+            })
+        });
+
+        let output = logs.contents();
+        assert_eq!(output.lines().count(), 2, "{output}");
+        assert!(output.contains("Request details"));
+        assert!(!output.contains("Loading settings"));
+    }
+}
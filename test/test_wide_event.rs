@@ -0,0 +1,163 @@
+//! Tests for canonical wide events
+//!
+//! Tests that `#[params(wide_event)]` emits exactly one summary event per call with the
+//! recorded fields, duration and outcome, on every exit path, under the function's target
+
+mod common;
+
+use common::capture_json;
+use log_args::params;
+
+#[derive(Debug)]
+struct CheckoutError;
+
+fn load_user(order_id: u64) -> Result<u64, CheckoutError> {
+    if order_id == 0 {
+        Err(CheckoutError)
+    } else {
+        Ok(order_id * 10)
+    }
+}
+
+#[params(wide_event, fields(order_id))]
+fn checkout(order_id: u64) -> Result<u64, CheckoutError> {
+    info!("Checkout started");
+    let user_id = load_user(order_id)?;
+    log_args_runtime::record!(user_id = user_id);
+    if order_id == 2 {
+        return Err(CheckoutError);
+    }
+    Ok(user_id)
+}
+
+#[params(wide_event, fields(step))]
+fn explode(step: u32) {
+    log_args_runtime::record!(reached = step);
+    panic!("step {step} failed");
+}
+
+#[params(wide_event)]
+fn labels() -> impl Iterator<Item = &'static str> {
+    log_args_runtime::record!(count = 2);
+    ["a", "b"].into_iter()
+}
+
+#[params(wide_event, fields(order_id))]
+async fn checkout_async(order_id: u64) -> Result<u64, CheckoutError> {
+    tokio::task::yield_now().await;
+    let user_id = load_user(order_id)?;
+    log_args_runtime::record!(user_id = user_id);
+    Ok(user_id)
+}
+
+#[params(wide_event, target = "billing", fields(invoice_id))]
+fn bill(invoice_id: u64) {
+    info!("Billing");
+}
+
+fn wide_events(lines: &[String]) -> Vec<&String> {
+    lines
+        .iter()
+        .filter(|line| line.contains("\"duration_ms\""))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_event_with_recorded_fields() {
+        let logs = capture_json(|| {
+            checkout(1).unwrap();
+        });
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2);
+        let events = wide_events(&lines);
+        assert_eq!(events.len(), 1);
+        let event = events[0];
+        assert!(event.contains(r#""level":"INFO""#), "{event}");
+        assert!(event.contains(r#""outcome":"ok""#), "{event}");
+        assert!(event.contains(r#""function":"checkout""#), "{event}");
+        assert!(event.contains(r#"\"order_id\": \"1\""#), "{event}");
+        assert!(event.contains(r#"\"user_id\": \"10\""#), "{event}");
+    }
+
+    #[test]
+    fn test_error_outcome_on_early_return_and_question_mark() {
+        let logs = capture_json(|| {
+            assert!(checkout(2).is_err());
+            assert!(checkout(0).is_err());
+        });
+
+        let lines = logs.lines();
+        let events = wide_events(&lines);
+        assert_eq!(events.len(), 2);
+        for event in &events {
+            assert!(event.contains(r#""level":"ERROR""#), "{event}");
+            assert!(event.contains(r#""outcome":"error""#), "{event}");
+        }
+        assert!(events[0].contains(r#"\"user_id\": \"20\""#));
+        assert!(!events[1].contains("user_id"));
+    }
+
+    #[test]
+    fn test_panic_outcome() {
+        let logs = capture_json(|| {
+            let result = std::panic::catch_unwind(|| explode(3));
+            assert!(result.is_err());
+        });
+
+        let lines = logs.lines();
+        let events = wide_events(&lines);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains(r#""outcome":"panic""#), "{}", events[0]);
+        assert!(events[0].contains(r#"\"reached\": \"3\""#), "{}", events[0]);
+    }
+
+    #[test]
+    fn test_impl_trait_return() {
+        let logs = capture_json(|| {
+            assert_eq!(labels().count(), 2);
+        });
+
+        let lines = logs.lines();
+        let events = wide_events(&lines);
+        assert_eq!(events.len(), 1);
+        assert!(events[0].contains(r#"\"count\": \"2\""#), "{}", events[0]);
+    }
+
+    #[test]
+    fn test_async_wide_event() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let logs = capture_json(|| {
+            assert_eq!(runtime.block_on(checkout_async(4)).unwrap(), 40);
+            assert!(runtime.block_on(checkout_async(0)).is_err());
+        });
+
+        let lines = logs.lines();
+        let events = wide_events(&lines);
+        assert_eq!(events.len(), 2);
+        assert!(events[0].contains(r#""outcome":"ok""#), "{}", events[0]);
+        assert!(
+            events[0].contains(r#"\"user_id\": \"40\""#),
+            "{}",
+            events[0]
+        );
+        assert!(events[1].contains(r#""outcome":"error""#), "{}", events[1]);
+    }
+
+    #[test]
+    fn test_summary_uses_function_target() {
+        let logs = capture_json(|| bill(5));
+
+        let lines = logs.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        for line in &lines {
+            assert!(line.contains(r#""target":"billing""#), "{line}");
+        }
+    }
+}